@@ -0,0 +1,223 @@
+use std::env;
+
+use anyhow::{bail, Result};
+use powerpack::{Key, Modifier};
+
+use crate::error::Error;
+use crate::Bookmark;
+
+/// What happens when a bookmark is actioned with a modifier key.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+    Open,
+    CopyUrl,
+    /// Copies a Markdown link like `[title](url)`.
+    CopyMarkdown,
+    AlternateBrowser,
+    PrivateWindow,
+    /// Points to Alfred's Quick Look, which is shown by tapping ⇧.
+    QuickLook,
+}
+
+/// The modifier keys that can be configured, with their variable, name as
+/// passed to the `run` command and symbol.
+const KEYS: [(Key, &str, &str, &str); 4] = [
+    (Key::Command, "MOD_CMD", "cmd", "⌘"),
+    (Key::Option, "MOD_ALT", "alt", "⌥"),
+    (Key::Control, "MOD_CTRL", "ctrl", "⌃"),
+    (Key::Shift, "MOD_SHIFT", "shift", "⇧"),
+];
+
+impl Action {
+    pub fn parse(name: &str) -> Result<Option<Action>> {
+        Ok(match name.trim() {
+            "none" => None,
+            "open" => Some(Action::Open),
+            "copy-url" => Some(Action::CopyUrl),
+            "copy-markdown" => Some(Action::CopyMarkdown),
+            "alternate-browser" => Some(Action::AlternateBrowser),
+            "private-window" => Some(Action::PrivateWindow),
+            "quicklook" => Some(Action::QuickLook),
+            _ => bail!("unknown action {}", name),
+        })
+    }
+}
+
+/// The actions of the modifier keys. Keys set to `none` have no action but
+/// still get a modifier, as the workflow connects all of them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Actions {
+    modifiers: Vec<(Key, Option<Action>)>,
+    /// The application used for the alternate browser and private windows.
+    pub browser: Option<String>,
+}
+
+impl Default for Actions {
+    fn default() -> Actions {
+        Actions {
+            modifiers: vec![
+                (Key::Command, Some(Action::CopyUrl)),
+                (Key::Option, Some(Action::CopyMarkdown)),
+                (Key::Control, Some(Action::AlternateBrowser)),
+                (Key::Shift, Some(Action::QuickLook)),
+            ],
+            browser: None,
+        }
+    }
+}
+
+impl Actions {
+    /// Actions without any modifiers.
+    pub fn none() -> Actions {
+        Actions {
+            modifiers: Vec::new(),
+            browser: None,
+        }
+    }
+
+    /// Sets the application used by the browser actions.
+    pub fn with_browser(mut self, browser: impl Into<String>) -> Actions {
+        self.browser = Some(browser.into());
+        self
+    }
+
+    /// Sets the action of a key, or none.
+    pub fn with_action(mut self, key: Key, action: Option<Action>) -> Actions {
+        match self.modifiers.iter_mut().find(|(other, _)| *other == key) {
+            Some((_, existing)) => *existing = action,
+            None => self.modifiers.push((key, action)),
+        }
+        self
+    }
+
+    /// Reads the actions from the `MOD_CMD`, `MOD_ALT`, `MOD_CTRL` and
+    /// `MOD_SHIFT` variables, falling back to the defaults, and the browser
+    /// from `ALTERNATE_BROWSER`.
+    pub fn from_env() -> Result<Actions, Error> {
+        let mut actions = Actions::default();
+        for (key, variable, _, _) in KEYS.iter() {
+            if let Ok(name) = env::var(variable).map(|name| name.trim().to_owned()) {
+                if !name.is_empty() {
+                    let action = Action::parse(&name).map_err(|error| Error::InvalidVariable {
+                        name: variable,
+                        message: error.to_string(),
+                    })?;
+                    actions = actions.with_action(*key, action);
+                }
+            }
+        }
+        actions.browser = env::var("ALTERNATE_BROWSER")
+            .ok()
+            .filter(|browser| !browser.trim().is_empty());
+        Ok(actions)
+    }
+
+    pub fn get(&self, key: Key) -> Option<Action> {
+        self.modifiers
+            .iter()
+            .find(|(modifier, _)| *modifier == key)
+            .and_then(|(_, action)| *action)
+    }
+
+    /// Returns the action of a key named like in the `run` command.
+    pub fn get_by_name(&self, name: &str) -> Result<Option<Action>> {
+        match KEYS.iter().find(|(_, _, key_name, _)| *key_name == name) {
+            Some((key, _, _, _)) => Ok(self.get(*key)),
+            None => bail!("unknown modifier key {}", name),
+        }
+    }
+
+    /// Returns the modifiers for the item of a bookmark. Keys without an
+    /// action, and browser actions without a configured browser, get a
+    /// modifier that can't be actioned and explains why, as Alfred would run
+    /// the workflow's connection for them otherwise.
+    pub fn modifiers(&self, bookmark: &Bookmark) -> Vec<Modifier> {
+        let urls = bookmark.urls().join("\n");
+        self.modifiers
+            .iter()
+            .map(|(key, action)| {
+                let modifier = Modifier::new(*key);
+                let action = match action {
+                    Some(action) => action,
+                    None => {
+                        let (variable, symbol) = KEYS
+                            .iter()
+                            .find(|(other, _, _, _)| other == key)
+                            .map_or(("MOD_*", "the key"), |(_, variable, _, symbol)| {
+                                (*variable, *symbol)
+                            });
+                        return modifier
+                            .subtitle(format!(
+                                "Nothing to do for {} · Set {} to choose an action",
+                                symbol, variable
+                            ))
+                            .valid(false);
+                    }
+                };
+                match (action, &self.browser) {
+                    (Action::Open, _) => modifier.subtitle("Open in browser").arg(urls.to_owned()),
+                    (Action::CopyUrl, _) => modifier.subtitle("Copy link").arg(urls.to_owned()),
+                    (Action::CopyMarkdown, _) => {
+                        let markdown = format!("[{}]({})", bookmark.name, bookmark.url());
+                        modifier
+                            .subtitle(format!("Copy {}", markdown))
+                            .arg(markdown)
+                    }
+                    (Action::AlternateBrowser, Some(browser)) => modifier
+                        .subtitle(format!("Open in {}", browser))
+                        .arg(urls.to_owned()),
+                    (Action::PrivateWindow, Some(browser)) => modifier
+                        .subtitle(format!("Open in a private window of {}", browser))
+                        .arg(urls.to_owned()),
+                    (Action::AlternateBrowser, None) | (Action::PrivateWindow, None) => modifier
+                        .subtitle("Set ALTERNATE_BROWSER to open in another browser")
+                        .valid(false),
+                    (Action::QuickLook, _) => {
+                        modifier.subtitle("Tap ⇧ for Quick Look").valid(false)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the command line option of a browser to open a private window.
+pub fn private_window_option(browser: &str) -> &'static str {
+    if browser.to_lowercase().contains("firefox") {
+        "--private-window"
+    } else {
+        "--incognito"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use powerpack::Key;
+
+    use crate::action::{private_window_option, Action, Actions};
+
+    #[test]
+    fn parses_actions() {
+        assert_eq!(
+            Action::parse("copy-markdown").unwrap(),
+            Some(Action::CopyMarkdown)
+        );
+        assert_eq!(Action::parse("none").unwrap(), None);
+        assert!(Action::parse("print").is_err());
+    }
+
+    #[test]
+    fn finds_actions_by_key_name() {
+        let actions = Actions::default();
+
+        assert_eq!(actions.get_by_name("cmd").unwrap(), Some(Action::CopyUrl));
+        assert_eq!(actions.get(Key::Shift), Some(Action::QuickLook));
+        assert!(actions.get_by_name("hyper").is_err());
+    }
+
+    #[test]
+    fn chooses_private_window_option() {
+        assert_eq!(private_window_option("Firefox"), "--private-window");
+        assert_eq!(private_window_option("Google Chrome"), "--incognito");
+    }
+}
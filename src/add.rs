@@ -0,0 +1,586 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use json::JsonValue;
+use powerpack::Item;
+
+use crate::lint::{self, check_link};
+use crate::sources;
+use crate::Bookmark;
+
+/// The category of bookmarks added without one.
+const DEFAULT_CATEGORY: &str = "Unsorted";
+
+/// A bookmark to add, typed like `add <url> <title> [in <category>]`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Addition {
+    pub link: String,
+    pub title: String,
+    pub category: Option<String>,
+    /// Adds the bookmark even if its link is already bookmarked, typed as
+    /// `add!`.
+    pub force: bool,
+}
+
+/// Parses a query adding a bookmark. Returns `None` if the query doesn't
+/// start with `add` followed by a link, and what is missing if the title is.
+pub fn parse(query: &str) -> Option<Result<Addition, String>> {
+    let mut words = query.split_whitespace();
+    let force = match words.next()? {
+        "add" => false,
+        "add!" => true,
+        _ => return None,
+    };
+    let link = words
+        .next()
+        .filter(|link| link.contains("://") || link.contains('.'))?;
+    let link = if link.contains("://") || link.starts_with("mailto:") {
+        link.to_owned()
+    } else {
+        format!("https://{}", link)
+    };
+    let words: Vec<&str> = words.collect();
+    let (title, category) = match words.iter().rposition(|word| *word == "in") {
+        Some(position) if position > 0 && position + 1 < words.len() => {
+            (&words[..position], Some(words[position + 1..].join(" ")))
+        }
+        _ => (&words[..], None),
+    };
+    if title.is_empty() {
+        return Some(Err(String::from("Type the title of the bookmark")));
+    }
+    Some(Ok(Addition {
+        link,
+        title: title.join(" "),
+        category,
+        force,
+    }))
+}
+
+/// Returns the first source in the json format of the workflow, which
+/// bookmarks are added to.
+pub fn json_file(sources: &str) -> Option<PathBuf> {
+    sources::paths(sources).ok()?.into_iter().find(|path| {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| lint::json_format(&contents))
+            .is_some()
+    })
+}
+
+/// Returns the name of the category to add to: an existing category matching
+/// the typed one regardless of case, the typed one or the default.
+fn category_name(parsed: &JsonValue, typed: Option<&str>) -> String {
+    let typed = typed.unwrap_or(DEFAULT_CATEGORY);
+    parsed
+        .entries()
+        .map(|(category, _)| category)
+        .find(|category| category.eq_ignore_ascii_case(typed))
+        .unwrap_or(typed)
+        .to_owned()
+}
+
+/// Returns the bookmark that already has the link, if any.
+pub fn duplicate<'a>(bookmarks: &'a [Bookmark], link: &str) -> Option<&'a Bookmark> {
+    let link = lint::normalize(link);
+    bookmarks.iter().find(|bookmark| {
+        std::iter::once(&bookmark.link)
+            .chain(bookmark.links.iter())
+            .any(|other| lint::normalize(other) == link)
+    })
+}
+
+/// Returns the Alfred item previewing the addition, which adds the bookmark
+/// when actioned. It can't be actioned if the link is invalid or already
+/// bookmarked without forcing it.
+pub fn item(query: &str, bookmarks: &[Bookmark], file: Option<&Path>) -> Item {
+    let addition = match parse(query) {
+        Some(Ok(addition)) => addition,
+        Some(Err(message)) => return Item::new("Add a bookmark").subtitle(message).valid(false),
+        None => return Item::new("Add a bookmark").valid(false),
+    };
+    let file = match file {
+        Some(file) => file,
+        None => {
+            return Item::new("Add a bookmark")
+                .subtitle("BOOKMARKS_FILE has no json file to add to")
+                .valid(false)
+        }
+    };
+    let category = fs::read_to_string(file)
+        .ok()
+        .and_then(|contents| json::parse(&contents).ok())
+        .map(|parsed| category_name(&parsed, addition.category.as_deref()))
+        .unwrap_or_else(|| DEFAULT_CATEGORY.to_owned());
+    let title = format!("Add \"{}\" to {}", addition.title, category);
+    if let Some((_, message)) = check_link(&addition.link) {
+        return Item::new(title).subtitle(message).valid(false);
+    }
+    match duplicate(bookmarks, &addition.link) {
+        Some(bookmark) if !addition.force => Item::new(title)
+            .subtitle(format!(
+                "Already bookmarked as \"{}\" · Type add! to add it anyway",
+                bookmark.name
+            ))
+            .autocomplete(format!("add!{}", &query.trim_start()[3..]))
+            .valid(false),
+        _ => Item::new(title)
+            .subtitle(format!("{} · Add to {} →", addition.link, file.display()))
+            .arg(query.trim().to_owned()),
+    }
+}
+
+/// Adds the bookmark to the json file, refusing links that are already
+/// bookmarked unless forced.
+pub fn add(file: &Path, addition: &Addition, bookmarks: &[Bookmark]) -> Result<()> {
+    if let Some((_, message)) = check_link(&addition.link) {
+        bail!("{}", message);
+    }
+    if let (Some(bookmark), false) = (duplicate(bookmarks, &addition.link), addition.force) {
+        bail!(
+            "{} is already bookmarked as \"{}\", use --force to add it anyway",
+            addition.link,
+            bookmark.name
+        );
+    }
+    let contents =
+        fs::read_to_string(file).with_context(|| format!("could not read {}", file.display()))?;
+    let parsed = json::parse(&contents)?;
+    let category = category_name(&parsed, addition.category.as_deref());
+    let added = insert(&contents, &category, &addition.link, &addition.title)?;
+    let temporary = file.with_extension("adding");
+    fs::write(&temporary, added)?;
+    fs::rename(&temporary, file).with_context(|| format!("could not write {}", file.display()))
+}
+
+/// The position of a value in the json text, from its first character to
+/// after its last.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A category of the top level object with the positions of its key, its
+/// list of bookmarks and the bookmarks in it.
+struct Category {
+    name: String,
+    key: Span,
+    value: Span,
+    members: Vec<Span>,
+}
+
+/// Adds a bookmark to a category of the json text, or to a new category at
+/// the end, keeping the formatting of the text and of the other bookmarks.
+pub fn insert(contents: &str, category: &str, link: &str, title: &str) -> Result<String> {
+    let (object, categories) = scan(contents).context("not a json object of categories")?;
+    let unit = categories
+        .first()
+        .map(|first| indentation(contents, first.key.start))
+        .filter(|indentation| !indentation.is_empty())
+        .unwrap_or("  ");
+    // Bookmarks are written like the last one of the category, or of the
+    // file for empty and new categories.
+    let sample = categories
+        .iter()
+        .rev()
+        .find_map(|category| category.members.last().copied());
+    // Without an indentation the fields are indented like those of the sample.
+    let entry = |like: Option<Span>, indent: Option<&str>| {
+        let fields = [
+            format!("\"href\": {}", json::stringify(link)),
+            format!("\"title\": {}", json::stringify(title)),
+        ];
+        match like.filter(|like| contents[like.start..like.end].contains('\n')) {
+            Some(like) => {
+                let (field_indent, closing_indent) = match indent {
+                    Some(indent) => (format!("{}{}", indent, unit), indent.to_owned()),
+                    None => {
+                        let field = contents[like.start..].find('"').unwrap_or_default();
+                        (
+                            indentation(contents, like.start + field).to_owned(),
+                            indentation(contents, like.end - 1).to_owned(),
+                        )
+                    }
+                };
+                format!(
+                    "{{\n{}{}\n{}}}",
+                    field_indent,
+                    fields.join(&format!(",\n{}", field_indent)),
+                    closing_indent
+                )
+            }
+            None => format!("{{{}}}", fields.join(", ")),
+        }
+    };
+
+    let mut added = contents.to_owned();
+    match categories.iter().find(|other| other.name == category) {
+        Some(existing) => match existing.members.last() {
+            Some(&last) => {
+                let indent = indentation(contents, last.start);
+                let own_line = contents[..last.start]
+                    .rsplit('\n')
+                    .next()
+                    .is_some_and(|before| before.trim().is_empty());
+                let separator = if own_line {
+                    format!(",\n{}", indent)
+                } else {
+                    String::from(", ")
+                };
+                added.insert_str(
+                    last.end,
+                    &format!("{}{}", separator, entry(Some(last), None)),
+                );
+            }
+            None if !contents[existing.value.start..].starts_with('[') => {
+                bail!("{} is not a list of bookmarks", category)
+            }
+            None => {
+                let indent = format!("{}{}", indentation(contents, existing.key.start), unit);
+                let list = format!(
+                    "[\n{}{}\n{}]",
+                    indent,
+                    entry(sample, Some(&indent)),
+                    indentation(contents, existing.key.start)
+                );
+                added.replace_range(existing.value.start..existing.value.end, &list);
+            }
+        },
+        None => {
+            let key_indent = categories
+                .last()
+                .map_or(unit, |last| indentation(contents, last.key.start));
+            let indent = format!("{}{}", key_indent, unit);
+            let new_category = format!(
+                "\n{}{}: [\n{}{}\n{}]",
+                key_indent,
+                json::stringify(category),
+                indent,
+                entry(sample, Some(&indent)),
+                key_indent
+            );
+            match categories.last() {
+                Some(last) => added.insert_str(last.value.end, &format!(",{}", new_category)),
+                None => added
+                    .replace_range(object.start..object.end, &format!("{{{}\n}}", new_category)),
+            }
+        }
+    }
+    let parsed = json::parse(&added).context("adding the bookmark broke the json")?;
+    if !parsed[category]
+        .members()
+        .any(|entry| entry["href"] == link)
+    {
+        bail!("the bookmark could not be added to {}", category);
+    }
+    Ok(added)
+}
+
+/// Returns the whitespace at the start of the line of a position.
+fn indentation(contents: &str, position: usize) -> &str {
+    let line_start = contents[..position]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let line = &contents[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Finds the categories of the top level object of a valid json text.
+fn scan(contents: &str) -> Option<(Span, Vec<Category>)> {
+    let bytes = contents.as_bytes();
+    let start = skip_whitespace(bytes, 0);
+    if bytes.get(start) != Some(&b'{') {
+        return None;
+    }
+    let object = Span {
+        start,
+        end: skip_value(bytes, start)?,
+    };
+    let categories = fields(bytes, object)?
+        .into_iter()
+        .map(|(key, value)| {
+            let name = json::parse(&contents[key.start..key.end]).ok()?;
+            Some(Category {
+                name: name.as_str()?.to_owned(),
+                key,
+                value,
+                members: members(bytes, value)?,
+            })
+        })
+        .collect::<Option<_>>()?;
+    Some((object, categories))
+}
+
+/// Finds the links of the bookmarks of a valid json text, the `href` values
+/// and the strings of `hrefs` lists.
+pub(crate) fn link_spans(contents: &str) -> Option<Vec<Span>> {
+    let bytes = contents.as_bytes();
+    let (_, categories) = scan(contents)?;
+    let mut links = Vec::new();
+    for member in categories.iter().flat_map(|category| &category.members) {
+        if bytes[member.start] != b'{' {
+            continue;
+        }
+        for (key, value) in fields(bytes, *member)? {
+            let key = json::parse(&contents[key.start..key.end]).ok()?;
+            match key.as_str()? {
+                "href" => links.push(value),
+                "hrefs" => links.extend(members(bytes, value)?),
+                _ => {}
+            }
+        }
+    }
+    links.retain(|link| bytes[link.start] == b'"');
+    Some(links)
+}
+
+/// Returns the positions of the keys and values of an object.
+fn fields(bytes: &[u8], object: Span) -> Option<Vec<(Span, Span)>> {
+    let mut fields = Vec::new();
+    let mut index = skip_whitespace(bytes, object.start + 1);
+    while bytes.get(index) == Some(&b'"') {
+        let key = Span {
+            start: index,
+            end: skip_string(bytes, index)?,
+        };
+        index = skip_whitespace(bytes, key.end);
+        if bytes.get(index) != Some(&b':') {
+            return None;
+        }
+        let value_start = skip_whitespace(bytes, index + 1);
+        let value = Span {
+            start: value_start,
+            end: skip_value(bytes, value_start)?,
+        };
+        fields.push((key, value));
+        index = skip_whitespace(bytes, value.end);
+        if bytes.get(index) == Some(&b',') {
+            index = skip_whitespace(bytes, index + 1);
+        }
+    }
+    if bytes.get(index) != Some(&b'}') {
+        return None;
+    }
+    Some(fields)
+}
+
+/// Returns the members of a list, or none for other values.
+fn members(bytes: &[u8], list: Span) -> Option<Vec<Span>> {
+    let mut members = Vec::new();
+    if bytes[list.start] != b'[' {
+        return Some(members);
+    }
+    let mut index = skip_whitespace(bytes, list.start + 1);
+    while index < list.end - 1 {
+        let end = skip_value(bytes, index)?;
+        members.push(Span { start: index, end });
+        index = skip_whitespace(bytes, end);
+        if bytes.get(index) == Some(&b',') {
+            index = skip_whitespace(bytes, index + 1);
+        }
+    }
+    Some(members)
+}
+
+fn skip_whitespace(bytes: &[u8], mut index: usize) -> usize {
+    while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+        index += 1;
+    }
+    index
+}
+
+/// Returns the position after the string starting at the index.
+fn skip_string(bytes: &[u8], mut index: usize) -> Option<usize> {
+    index += 1;
+    loop {
+        match bytes.get(index)? {
+            b'\\' => index += 2,
+            b'"' => return Some(index + 1),
+            _ => index += 1,
+        }
+    }
+}
+
+/// Returns the position after the value starting at the index.
+fn skip_value(bytes: &[u8], index: usize) -> Option<usize> {
+    match bytes.get(index)? {
+        b'"' => skip_string(bytes, index),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut index = index;
+            loop {
+                match bytes.get(index)? {
+                    b'"' => {
+                        index = skip_string(bytes, index)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(index + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+        }
+        _ => {
+            let mut index = index;
+            while bytes
+                .get(index)
+                .is_some_and(|byte| !b",}] \t\r\n".contains(byte))
+            {
+                index += 1;
+            }
+            Some(index)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::add::{add, duplicate, insert, parse, Addition};
+    use crate::test_support::TempDir;
+    use crate::Bookmark;
+
+    #[test]
+    fn parses_additions() {
+        assert_eq!(
+            parse("add https://jira.test.blub/ Jira board in work tools"),
+            Some(Ok(Addition {
+                link: "https://jira.test.blub/".to_owned(),
+                title: "Jira board".to_owned(),
+                category: Some("work tools".to_owned()),
+                force: false,
+            }))
+        );
+        assert_eq!(
+            parse("add! wiki.test.blub Log in page in tools"),
+            Some(Ok(Addition {
+                link: "https://wiki.test.blub".to_owned(),
+                title: "Log in page".to_owned(),
+                category: Some("tools".to_owned()),
+                force: true,
+            }))
+        );
+        assert_eq!(
+            parse("add jira.test.blub"),
+            Some(Err("Type the title of the bookmark".to_owned()))
+        );
+        assert_eq!(
+            parse("add mailto:team@test.blub Team")
+                .unwrap()
+                .unwrap()
+                .link,
+            "mailto:team@test.blub"
+        );
+        assert_eq!(parse("add dashboard"), None);
+        assert_eq!(parse("jira"), None);
+    }
+
+    #[test]
+    fn adds_to_a_category_keeping_the_formatting() {
+        let contents = "{\n\t\"work\": [{\n\t\t\t\"href\": \"https://jira.test.blub\",\n\t\t\t\"title\": \"Jira\"\n\t\t}\n\t],\n\t\"private\": [{\n\t\t\"href\": \"http://www.test.blub\",\n\t\t\"title\": \"Dashboard\"\n\t}]\n}\n";
+        let expected = "{\n\t\"work\": [{\n\t\t\t\"href\": \"https://jira.test.blub\",\n\t\t\t\"title\": \"Jira\"\n\t\t}, {\n\t\t\t\"href\": \"https://wiki.test.blub\",\n\t\t\t\"title\": \"Wiki \\\"team\\\"\"\n\t\t}\n\t],\n\t\"private\": [{\n\t\t\"href\": \"http://www.test.blub\",\n\t\t\"title\": \"Dashboard\"\n\t}]\n}\n";
+
+        let added = insert(contents, "work", "https://wiki.test.blub", "Wiki \"team\"").unwrap();
+
+        assert_eq!(added, expected);
+    }
+
+    #[test]
+    fn adds_after_the_last_bookmark_on_its_own_line() {
+        let contents = r#"{
+  "work": [
+    {"href": "https://jira.test.blub", "title": "Jira"}
+  ],
+  "empty": []
+}"#;
+        let expected = r#"{
+  "work": [
+    {"href": "https://jira.test.blub", "title": "Jira"},
+    {"href": "https://wiki.test.blub", "title": "Wiki"}
+  ],
+  "empty": []
+}"#;
+
+        let added = insert(contents, "work", "https://wiki.test.blub", "Wiki").unwrap();
+
+        assert_eq!(added, expected);
+    }
+
+    #[test]
+    fn adds_to_empty_and_new_categories() {
+        let contents = r#"{
+  "work": [
+    {"href": "https://jira.test.blub", "title": "Jira"}
+  ],
+  "empty": []
+}"#;
+        let expected = r#"{
+  "work": [
+    {"href": "https://jira.test.blub", "title": "Jira"}
+  ],
+  "empty": [
+    {"href": "https://wiki.test.blub", "title": "Wiki"}
+  ],
+  "Unsorted": [
+    {"href": "http://www.test.blub", "title": "Dashboard"}
+  ]
+}"#;
+
+        let added = insert(contents, "empty", "https://wiki.test.blub", "Wiki").unwrap();
+        let added = insert(&added, "Unsorted", "http://www.test.blub", "Dashboard").unwrap();
+
+        assert_eq!(added, expected);
+        assert_eq!(
+            insert("{}", "work", "https://jira.test.blub", "Jira").unwrap(),
+            "{\n  \"work\": [\n    {\"href\": \"https://jira.test.blub\", \"title\": \"Jira\"}\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn finds_duplicates() {
+        let bookmarks = vec![Bookmark::new("Jira", "https://jira.test.blub/")];
+
+        assert_eq!(
+            duplicate(&bookmarks, "https://JIRA.test.blub"),
+            Some(&bookmarks[0])
+        );
+        assert_eq!(duplicate(&bookmarks, "https://wiki.test.blub"), None);
+    }
+
+    #[test]
+    fn refuses_duplicates_unless_forced() {
+        let directory = TempDir::new("add");
+        let path = directory.join("bookmarks.json");
+        let contents = r#"{"work": [{"href": "https://jira.test.blub", "title": "Jira"}]}"#;
+        fs::write(&path, contents).unwrap();
+        let bookmarks = vec![Bookmark::new("Jira", "https://jira.test.blub")];
+        let mut addition = Addition {
+            link: "https://jira.test.blub/".to_owned(),
+            title: "Jira again".to_owned(),
+            category: Some("Work".to_owned()),
+            force: false,
+        };
+
+        let refused = add(&path, &addition, &bookmarks);
+        let unchanged = fs::read_to_string(&path).unwrap();
+        addition.force = true;
+        add(&path, &addition, &bookmarks).unwrap();
+        let added = fs::read_to_string(&path).unwrap();
+
+        assert!(refused.is_err());
+        assert_eq!(unchanged, contents);
+        assert_eq!(
+            added,
+            r#"{"work": [{"href": "https://jira.test.blub", "title": "Jira"}, {"href": "https://jira.test.blub/", "title": "Jira again"}]}"#
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use powerpack::{Icon, Item};
+
+const CAUTION_ICON: &str =
+    "/System/Library/CoreServices/CoreTypes.bundle/Contents/Resources/AlertCautionIcon.icns";
+
+/// What went wrong while searching.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Error {
+    /// A workflow variable that has to be set is missing.
+    MissingVariable(&'static str),
+    /// A workflow variable has a value that can't be used.
+    InvalidVariable { name: &'static str, message: String },
+    /// A bookmark file can't be read.
+    File { path: PathBuf, problem: Problem },
+}
+
+/// Why a bookmark file can't be read.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Problem {
+    NotFound,
+    Unreadable(String),
+    /// The file is no valid json, with the line and column of the error if
+    /// known.
+    InvalidJson {
+        position: Option<(usize, usize)>,
+        message: String,
+    },
+    /// An entry of a category, or the category itself without an index,
+    /// doesn't have the expected fields.
+    InvalidEntry {
+        category: String,
+        index: Option<usize>,
+        message: String,
+    },
+}
+
+impl From<json::Error> for Problem {
+    fn from(error: json::Error) -> Problem {
+        match error {
+            json::Error::UnexpectedCharacter { ch, line, column } => Problem::InvalidJson {
+                position: Some((line, column)),
+                message: format!("unexpected character {}", ch),
+            },
+            error => Problem::InvalidJson {
+                position: None,
+                message: error.to_string().to_lowercase(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NotFound => write!(f, "does not exist"),
+            Problem::Unreadable(message) => write!(f, "{}", message),
+            Problem::InvalidJson {
+                position: Some((line, column)),
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            Problem::InvalidJson {
+                position: None,
+                message,
+            } => write!(f, "{}", message),
+            Problem::InvalidEntry {
+                category,
+                index: Some(index),
+                message,
+            } => write!(f, "entry {} of \"{}\": {}", index + 1, category, message),
+            Problem::InvalidEntry {
+                category,
+                index: None,
+                message,
+            } => write!(f, "\"{}\": {}", category, message),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingVariable(name) => write!(f, "{} is not set", name),
+            Error::InvalidVariable { name, message } => {
+                write!(f, "{} is invalid: {}", name, message)
+            }
+            Error::File { path, problem } => write!(f, "{}: {}", path.display(), problem),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Returns the url opening the workflow in Alfred's preferences, or the list
+/// of workflows when run outside of Alfred.
+fn configuration_url() -> String {
+    match powerpack::env::workflow_uid() {
+        Some(uid) => format!("alfredpreferences://navigateto/workflows>workflow>{}", uid),
+        None => String::from("alfredpreferences://navigateto/workflows"),
+    }
+}
+
+impl Error {
+    /// Returns an Alfred item explaining the error, which opens the broken
+    /// file or else the workflow configuration.
+    pub fn to_item(&self) -> Item {
+        let (title, details, arg) = match self {
+            Error::MissingVariable(name) => (
+                format!("{} is not set", name),
+                String::from("Set it in the workflow configuration →"),
+                configuration_url(),
+            ),
+            Error::InvalidVariable { name, message } => (
+                format!("{} is invalid", name),
+                format!("{} · Fix it in the workflow configuration →", message),
+                configuration_url(),
+            ),
+            Error::File {
+                path,
+                problem: Problem::NotFound,
+            } => (
+                format!("{} does not exist", path.display()),
+                String::from("Fix BOOKMARKS_FILE in the workflow configuration →"),
+                configuration_url(),
+            ),
+            Error::File { path, problem } => (
+                format!("Could not read {}", path.display()),
+                format!("{} · Open the file →", problem),
+                path.display().to_string(),
+            ),
+        };
+        Item::new(title)
+            .subtitle(details)
+            .arg(arg)
+            .icon(Icon::with_image(CAUTION_ICON))
+    }
+}
+
+/// Returns a single item telling how many bookmarks, and files of
+/// directories, were skipped because they are invalid, explaining the first
+/// one.
+pub fn skipped_item(skipped: &[Error]) -> Option<Item> {
+    let first = skipped.first()?;
+    let entries = skipped
+        .iter()
+        .filter(|error| {
+            matches!(
+                error,
+                Error::File {
+                    problem: Problem::InvalidEntry { .. },
+                    ..
+                }
+            )
+        })
+        .count();
+    let counted = |count, noun| match count {
+        1 => format!("1 {}", noun),
+        count => format!("{} {}s", count, noun),
+    };
+    let counts: Vec<String> = [(entries, "bookmark"), (skipped.len() - entries, "file")]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, noun)| counted(*count, noun))
+        .collect();
+    let title = match skipped.len() {
+        1 => format!("{} was skipped", counts.join(" and ")),
+        _ => format!("{} were skipped", counts.join(" and ")),
+    };
+    let (subtitle, arg) = match first {
+        Error::File { path, problem } => (
+            format!(
+                "{}: {} · Open the file →",
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                problem
+            ),
+            path.display().to_string(),
+        ),
+        error => (error.to_string(), configuration_url()),
+    };
+    Some(
+        Item::new(title)
+            .subtitle(subtitle)
+            .arg(arg)
+            .icon(Icon::with_image(CAUTION_ICON)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use powerpack::{Icon, Item};
+
+    use crate::error::{skipped_item, Error, Problem, CAUTION_ICON};
+
+    #[test]
+    fn explains_invalid_json_with_its_position() {
+        let error = Error::File {
+            path: PathBuf::from("/bookmarks.json"),
+            problem: Problem::from(json::parse("{\n  \"work\": [}").unwrap_err()),
+        };
+        let expected_item = Item::new("Could not read /bookmarks.json")
+            .subtitle("line 2, column 12: unexpected character } · Open the file →")
+            .arg("/bookmarks.json")
+            .icon(Icon::with_image(CAUTION_ICON));
+
+        assert_eq!(error.to_item(), expected_item);
+    }
+
+    #[test]
+    fn explains_invalid_entries() {
+        let error = Error::File {
+            path: PathBuf::from("/bookmarks.json"),
+            problem: Problem::InvalidEntry {
+                category: String::from("work"),
+                index: Some(1),
+                message: String::from("missing href"),
+            },
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "/bookmarks.json: entry 2 of \"work\": missing href"
+        );
+    }
+
+    #[test]
+    fn sums_up_skipped_bookmarks() {
+        let skipped = |index| Error::File {
+            path: PathBuf::from("/team/bookmarks.json"),
+            problem: Problem::InvalidEntry {
+                category: String::from("work"),
+                index: Some(index),
+                message: String::from("missing title"),
+            },
+        };
+        let expected_item = Item::new("2 bookmarks were skipped")
+            .subtitle("bookmarks.json: entry 1 of \"work\": missing title · Open the file →")
+            .arg("/team/bookmarks.json")
+            .icon(Icon::with_image(CAUTION_ICON));
+
+        assert_eq!(skipped_item(&[]), None);
+        assert_eq!(skipped_item(&[skipped(0), skipped(3)]), Some(expected_item));
+    }
+
+    #[test]
+    fn counts_skipped_files_apart_from_bookmarks() {
+        let entry = Error::File {
+            path: PathBuf::from("/team/bookmarks.json"),
+            problem: Problem::InvalidEntry {
+                category: String::from("work"),
+                index: Some(0),
+                message: String::from("missing title"),
+            },
+        };
+        let file = Error::File {
+            path: PathBuf::from("/team/notes.txt"),
+            problem: Problem::Unreadable(String::from("not a text file")),
+        };
+
+        assert_eq!(
+            skipped_item(&[entry, file.clone()]).unwrap(),
+            Item::new("1 bookmark and 1 file were skipped")
+                .subtitle("bookmarks.json: entry 1 of \"work\": missing title · Open the file →")
+                .arg("/team/bookmarks.json")
+                .icon(Icon::with_image(CAUTION_ICON))
+        );
+        assert_eq!(
+            skipped_item(&[file]).unwrap(),
+            Item::new("1 file was skipped")
+                .subtitle("notes.txt: not a text file · Open the file →")
+                .arg("/team/notes.txt")
+                .icon(Icon::with_image(CAUTION_ICON))
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::field::split_link;
+use crate::history::BUNDLE_ID;
+use crate::Bookmark;
+
+const DIRECTORY_NAME: &str = "favicons";
+
+/// How many favicons are fetched at the same time.
+const CONCURRENT_FETCHES: usize = 8;
+
+/// How long fetching a single favicon may take, in seconds.
+const TIMEOUT: &str = "10";
+
+/// Returns the directory in the workflow cache holding the favicons.
+pub fn directory() -> Option<PathBuf> {
+    let cache = powerpack::env::workflow_cache().or_else(|| {
+        let home = PathBuf::from(env::var_os("HOME")?);
+        Some(
+            home.join("Library/Caches/com.runningwithcrayons.Alfred/Workflow Data")
+                .join(BUNDLE_ID),
+        )
+    })?;
+    Some(cache.join(DIRECTORY_NAME))
+}
+
+/// Returns the path of the favicon of a host, e.g. `github.com.ico`.
+pub fn path(directory: &Path, host: &str) -> PathBuf {
+    directory.join(format!("{}.ico", host.replace(':', "_")))
+}
+
+/// Returns the url of the favicon of the host of an http(s) link.
+fn url(link: &str) -> Option<String> {
+    let scheme = link
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_lowercase())
+        .filter(|scheme| scheme == "http" || scheme == "https")?;
+    let (host, _) = split_link(link);
+    Some(format!("{}://{}/favicon.ico", scheme, host)).filter(|_| !host.is_empty())
+}
+
+/// How many favicons were fetched and how many failed.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Summary {
+    pub fetched: usize,
+    pub failed: usize,
+}
+
+/// Fetches the favicon of every host of the bookmarks into the directory.
+/// A favicon that can't be fetched keeps the one fetched before.
+pub fn refresh(bookmarks: &[Bookmark], directory: &Path) -> Result<Summary> {
+    fs::create_dir_all(directory)?;
+    let hosts: Vec<(String, String)> = bookmarks
+        .iter()
+        .flat_map(|bookmark| std::iter::once(&bookmark.link).chain(bookmark.links.iter()))
+        .filter_map(|link| Some((split_link(link).0.to_lowercase(), url(link)?)))
+        .unique_by(|(host, _)| host.to_owned())
+        .collect();
+
+    let mut summary = Summary::default();
+    for chunk in hosts.chunks(CONCURRENT_FETCHES) {
+        let fetches: Vec<(&str, PathBuf, Result<Child>)> = chunk
+            .iter()
+            .map(|(host, url)| {
+                let download = path(directory, host).with_extension("download");
+                let child = fetch(url, &download);
+                (host.as_str(), download, child)
+            })
+            .collect();
+        for (host, download, child) in fetches {
+            let fetched = child
+                .and_then(|mut child| Ok(child.wait()?.success()))
+                .unwrap_or(false)
+                && is_image(&download);
+            if fetched {
+                fs::rename(&download, path(directory, host))?;
+                summary.fetched += 1;
+            } else {
+                let _ = fs::remove_file(&download);
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn fetch(url: &str, target: &Path) -> Result<Child> {
+    Ok(Command::new("curl")
+        .args(["--silent", "--fail", "--location", "--max-time", TIMEOUT])
+        .arg("--output")
+        .arg(target)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?)
+}
+
+/// Returns false for empty downloads and for html pages some servers send
+/// instead of a missing favicon.
+fn is_image(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|content| {
+        let start = String::from_utf8_lossy(&content[..content.len().min(64)]).to_lowercase();
+        !content.is_empty() && !start.trim_start().starts_with('<')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::favicon::{path, refresh, url, Summary};
+    use crate::test_support::{closed_port, serve, TempDir};
+    use crate::Bookmark;
+
+    #[test]
+    fn builds_the_favicon_url() {
+        assert_eq!(
+            url("https://user@jira.test.blub:8443/browse?q=1"),
+            Some("https://jira.test.blub:8443/favicon.ico".to_owned())
+        );
+        assert_eq!(url("ftp://files.test.blub/a"), None);
+        assert_eq!(url("javascript:alert(1)"), None);
+    }
+
+    /// Serves a favicon for `/favicon.ico` and a html page for anything else.
+    fn serve_favicons() -> u16 {
+        serve(|path| {
+            let body = match path {
+                "/favicon.ico" => "\0\0\x01\0icon",
+                _ => "<html></html>",
+            };
+            (String::from("200 OK"), String::from(body))
+        })
+    }
+
+    #[test]
+    fn fetches_the_favicon_of_every_host() {
+        let port = serve_favicons();
+        let closed_port = closed_port();
+        let directory = TempDir::new("favicons");
+        let bookmarks = vec![
+            Bookmark::new("a", format!("http://127.0.0.1:{}/a", port)),
+            Bookmark::new("b", format!("http://127.0.0.1:{}/b", port)),
+            Bookmark::new("c", format!("http://127.0.0.1:{}/c", closed_port)),
+            Bookmark::new("d", "file:///Users/me/notes.txt"),
+        ];
+
+        let summary = refresh(&bookmarks, directory.path()).unwrap();
+        let favicon = fs::read(path(directory.path(), &format!("127.0.0.1:{}", port))).unwrap();
+        let missing = path(directory.path(), &format!("127.0.0.1:{}", closed_port)).exists();
+
+        assert_eq!(
+            summary,
+            Summary {
+                fetched: 1,
+                failed: 1
+            }
+        );
+        assert_eq!(favicon, b"\0\0\x01\0icon");
+        assert!(!missing);
+    }
+}
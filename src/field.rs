@@ -0,0 +1,119 @@
+use anyhow::{bail, Result};
+
+use crate::Bookmark;
+
+/// A part of a bookmark that queries are matched against.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Field {
+    Title,
+    /// All folders of the category.
+    Category,
+    /// The host of the link, e.g. `grafana.example.com`.
+    Host,
+    /// Everything after the host, e.g. `/d/prod?orgId=1`.
+    Path,
+    Tags,
+    /// The note of bookmarks read from bookmark exports.
+    Description,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::Title,
+        Field::Category,
+        Field::Host,
+        Field::Path,
+        Field::Tags,
+        Field::Description,
+    ];
+
+    /// Parses a comma separated list of fields like `title,host`.
+    pub fn parse_list(fields: &str) -> Result<Vec<Field>> {
+        fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(|field| match field.to_ascii_lowercase().as_str() {
+                "title" => Ok(Field::Title),
+                "category" => Ok(Field::Category),
+                "host" => Ok(Field::Host),
+                "path" => Ok(Field::Path),
+                "tags" => Ok(Field::Tags),
+                "description" => Ok(Field::Description),
+                _ => bail!("unknown search field {}", field),
+            })
+            .collect()
+    }
+
+    /// The weight of a match in this field in percent, so that title matches
+    /// rank above link matches.
+    pub fn weight(self) -> i64 {
+        match self {
+            Field::Title => 100,
+            Field::Category => 80,
+            Field::Tags => 70,
+            Field::Host => 60,
+            Field::Path => 40,
+            Field::Description => 30,
+        }
+    }
+
+    pub fn value(self, bookmark: &Bookmark) -> String {
+        match self {
+            Field::Title => bookmark.name.to_owned(),
+            Field::Category => bookmark.path.join(" "),
+            Field::Host => split_link(&bookmark.link).0.to_owned(),
+            Field::Path => split_link(&bookmark.link).1.to_owned(),
+            Field::Tags => bookmark.tags.join(" "),
+            Field::Description => bookmark.description.to_owned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Splits a link into its host and the rest after the host.
+pub fn split_link(link: &str) -> (&str, &str) {
+    let without_scheme = link.split_once("://").map_or(link, |(_, rest)| rest);
+    let host_end = without_scheme
+        .find(['/', '?', '#'])
+        .unwrap_or(without_scheme.len());
+    let (host, path) = without_scheme.split_at(host_end);
+    let host = host.rsplit('@').next().unwrap_or(host);
+    (host, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::{split_link, Field};
+    use crate::Bookmark;
+
+    #[test]
+    fn parses_field_lists() {
+        assert_eq!(
+            Field::parse_list("Title, host").unwrap(),
+            vec![Field::Title, Field::Host]
+        );
+        assert!(Field::parse_list("title,colour").is_err());
+    }
+
+    #[test]
+    fn matches_descriptions() {
+        let bookmark =
+            Bookmark::new("Jira", "https://jira.test.blub").with_description("where tickets live");
+
+        assert_eq!(Field::Description.value(&bookmark), "where tickets live");
+        assert_eq!(Field::Description.value(&Bookmark::new("Jira", "")), "");
+    }
+
+    #[test]
+    fn splits_links() {
+        assert_eq!(
+            split_link("https://grafana.test.blub/d/prod?orgId=1"),
+            ("grafana.test.blub", "/d/prod?orgId=1")
+        );
+        assert_eq!(
+            split_link("http://user@www.test.blub"),
+            ("www.test.blub", "")
+        );
+        assert_eq!(split_link("www.test.blub/path"), ("www.test.blub", "/path"));
+    }
+}
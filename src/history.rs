@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use itertools::Itertools;
+use json::{object, JsonValue};
+
+const FILE_NAME: &str = "history.json";
+
+/// The last query typed, to know which query a bookmark was chosen for.
+const LAST_QUERY_FILE_NAME: &str = "last-query";
+
+/// How many visits per bookmark are kept to calculate the frecency.
+const MAX_VISITS: usize = 10;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// How often a bookmark was opened.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Usage {
+    pub count: u64,
+    /// The most recent visits in seconds since the unix epoch, oldest first.
+    pub visits: Vec<u64>,
+}
+
+/// The usage of bookmarks, stored in the workflow data directory.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct History {
+    usages: HashMap<String, Usage>,
+    /// How often each bookmark was chosen for a query.
+    queries: HashMap<String, HashMap<String, u64>>,
+}
+
+/// The bundle id of the workflow, to find its data directory when run
+/// outside of Alfred.
+pub const BUNDLE_ID: &str = "sejoharp.bookmarks";
+
+fn data_directory() -> Option<PathBuf> {
+    powerpack::env::workflow_data().or_else(|| {
+        let home = PathBuf::from(env::var_os("HOME")?);
+        Some(
+            home.join("Library/Application Support/Alfred/Workflow Data")
+                .join(BUNDLE_ID),
+        )
+    })
+}
+
+/// Returns the path of the history file in the workflow data directory.
+pub fn path() -> Option<PathBuf> {
+    Some(data_directory()?.join(FILE_NAME))
+}
+
+/// Returns the path of the file holding the last query typed.
+pub fn last_query_path() -> Option<PathBuf> {
+    Some(data_directory()?.join(LAST_QUERY_FILE_NAME))
+}
+
+/// Normalizes a query, so that associations don't depend on case or
+/// surrounding whitespace.
+pub fn normalize_query(query: &str) -> String {
+    query.split_whitespace().join(" ").to_lowercase()
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+impl History {
+    /// Loads the history, which is empty if it was never saved.
+    pub fn load(path: &Path) -> Result<History> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(History::default()),
+            Err(error) => return Err(error.into()),
+        };
+        let parsed = json::parse(&contents)?;
+        let usages = parsed["bookmarks"]
+            .entries()
+            .map(|(link, usage)| {
+                let usage = Usage {
+                    count: usage["count"].as_u64().unwrap_or_default(),
+                    visits: usage["visits"]
+                        .members()
+                        .filter_map(JsonValue::as_u64)
+                        .collect(),
+                };
+                (link.to_owned(), usage)
+            })
+            .collect();
+        let queries = parsed["queries"]
+            .entries()
+            .map(|(query, links)| {
+                let links = links
+                    .entries()
+                    .map(|(link, count)| (link.to_owned(), count.as_u64().unwrap_or_default()))
+                    .collect();
+                (query.to_owned(), links)
+            })
+            .collect();
+        Ok(History { usages, queries })
+    }
+
+    /// Saves the history through a temporary file of the process, so that
+    /// an interrupted or concurrent save never leaves a truncated file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let temporary = path.with_extension(format!("{}.saving", process::id()));
+        fs::write(&temporary, self.to_json().pretty(2))?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut history = object! {
+            bookmarks: JsonValue::new_object(),
+            queries: JsonValue::new_object(),
+        };
+        for (link, usage) in self.usages.iter() {
+            history["bookmarks"][link.as_str()] = object! {
+                count: usage.count,
+                visits: usage.visits.clone(),
+            };
+        }
+        for (query, links) in self.queries.iter() {
+            for (link, count) in links.iter() {
+                history["queries"][query.as_str()][link.as_str()] = (*count).into();
+            }
+        }
+        history
+    }
+
+    pub fn record(&mut self, link: &str, now: u64) {
+        let usage = self.usages.entry(link.to_owned()).or_default();
+        usage.count += 1;
+        usage.visits.push(now);
+        if usage.visits.len() > MAX_VISITS {
+            usage.visits.remove(0);
+        }
+    }
+
+    /// Remembers that the bookmark was chosen for the query.
+    pub fn record_query(&mut self, query: &str, link: &str) {
+        let query = normalize_query(query);
+        if query.is_empty() {
+            return;
+        }
+        *self
+            .queries
+            .entry(query)
+            .or_default()
+            .entry(link.to_owned())
+            .or_default() += 1;
+    }
+
+    /// Returns the amount to improve the matching score of a bookmark by,
+    /// if it was chosen before for the query or the start of it. This
+    /// outweighs small differences in the fuzzy score.
+    pub fn query_boost(&self, query: &str, link: &str) -> i64 {
+        let query = normalize_query(query);
+        let count: u64 = self
+            .queries
+            .iter()
+            .filter(|(chosen_for, _)| query.starts_with(chosen_for.as_str()))
+            .filter_map(|(_, links)| links.get(link))
+            .sum();
+        (25.0 * (1.0 + count as f64).log2()) as i64
+    }
+
+    /// Calculates the frecency like Firefox does: recent visits weigh more
+    /// than old ones, scaled up to the total number of visits.
+    pub fn frecency(&self, link: &str, now: u64) -> u64 {
+        let usage = match self.usages.get(link) {
+            Some(usage) if !usage.visits.is_empty() => usage,
+            _ => return 0,
+        };
+        let weights: u64 = usage
+            .visits
+            .iter()
+            .map(|visit| match now.saturating_sub(*visit) / DAY {
+                0..=3 => 100,
+                4..=13 => 70,
+                14..=30 => 50,
+                31..=90 => 30,
+                _ => 10,
+            })
+            .sum();
+        weights * usage.count / usage.visits.len() as u64
+    }
+
+    /// Returns the amount to improve the matching score of a bookmark by. It
+    /// grows logarithmically, so that frequent use helps a lot at first
+    /// without drowning the fuzzy score.
+    pub fn boost(&self, link: &str, now: u64) -> i64 {
+        let frecency = self.frecency(link, now) as f64;
+        (8.0 * (1.0 + frecency / 10.0).log2()) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{History, Usage, DAY, MAX_VISITS};
+    use crate::test_support::TempDir;
+
+    const NOW: u64 = 1_650_000_000;
+
+    #[test]
+    fn records_visits() {
+        let mut history = History::default();
+
+        for visit in 0..12 {
+            history.record("http://www.test.blub", NOW + visit);
+        }
+
+        let usage = &history.usages["http://www.test.blub"];
+        assert_eq!(usage.count, 12);
+        assert_eq!(usage.visits.len(), MAX_VISITS);
+        assert_eq!(usage.visits[0], NOW + 2);
+    }
+
+    #[test]
+    fn prefers_recent_and_frequent_visits() {
+        let mut history = History::default();
+        history.record("http://recent.blub", NOW);
+        history.record("http://old.blub", NOW - 100 * DAY);
+        history.record("http://frequent.blub", NOW);
+        history.record("http://frequent.blub", NOW);
+
+        assert_eq!(history.frecency("http://recent.blub", NOW), 100);
+        assert_eq!(history.frecency("http://old.blub", NOW), 10);
+        assert_eq!(history.frecency("http://frequent.blub", NOW), 200);
+        assert_eq!(history.frecency("http://never.blub", NOW), 0);
+        assert!(
+            history.boost("http://frequent.blub", NOW) > history.boost("http://recent.blub", NOW)
+        );
+        assert_eq!(history.boost("http://never.blub", NOW), 0);
+    }
+
+    #[test]
+    fn boosts_bookmarks_chosen_for_the_start_of_the_query() {
+        let mut history = History::default();
+        history.record_query(" Gr ", "https://grafana.test.blub/d/prod");
+
+        assert_eq!(
+            history.query_boost("gr", "https://grafana.test.blub/d/prod"),
+            25
+        );
+        assert_eq!(
+            history.query_boost("graf", "https://grafana.test.blub/d/prod"),
+            25
+        );
+        assert_eq!(
+            history.query_boost("g", "https://grafana.test.blub/d/prod"),
+            0
+        );
+        assert_eq!(
+            history.query_boost("gr", "https://grafana.test.blub/d/stage"),
+            0
+        );
+    }
+
+    #[test]
+    fn ignores_empty_queries() {
+        let mut history = History::default();
+        history.record_query("  ", "http://www.test.blub");
+
+        assert_eq!(history, History::default());
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let directory = TempDir::new("history");
+        let path = directory.join("history.json");
+        let mut history = History::default();
+        history.record("http://www.test.blub", NOW);
+        history.record_query("te", "http://www.test.blub");
+
+        history.save(&path).unwrap();
+        let loaded = History::load(&path).unwrap();
+        let files = std::fs::read_dir(directory.path()).unwrap().count();
+
+        assert_eq!(loaded, history);
+        assert_eq!(files, 1);
+        assert_eq!(
+            loaded.usages["http://www.test.blub"],
+            Usage {
+                count: 1,
+                visits: vec![NOW],
+            }
+        );
+    }
+
+    #[test]
+    fn loads_missing_history_as_empty() {
+        let directory = TempDir::new("history-missing");
+        let path = directory.join("history.json");
+
+        assert_eq!(History::load(&path).unwrap(), History::default());
+    }
+}
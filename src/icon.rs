@@ -0,0 +1,150 @@
+use std::env;
+use std::path::PathBuf;
+
+use powerpack::Icon;
+
+use crate::favicon;
+use crate::field::split_link;
+use crate::Bookmark;
+
+/// The icons shown for categories and bookmarks.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Icons {
+    /// The icon of each category, also used for its subfolders.
+    categories: Vec<(String, String)>,
+    /// The directory holding the favicons fetched per host.
+    favicons: Option<PathBuf>,
+}
+
+impl Icons {
+    /// Parses `;` separated category icons like
+    /// `work=icons/work.png;Bookmarks Menu=icons/menu.png`. Paths can be
+    /// relative to the workflow directory.
+    pub fn parse_list(icons: &str) -> Icons {
+        let categories = icons
+            .split(';')
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(category, icon)| (category.trim().to_owned(), icon.trim().to_owned()))
+            .filter(|(category, icon)| !category.is_empty() && !icon.is_empty())
+            .collect();
+        Icons {
+            categories,
+            favicons: None,
+        }
+    }
+
+    pub fn with_favicons(mut self, directory: impl Into<PathBuf>) -> Icons {
+        self.favicons = Some(directory.into());
+        self
+    }
+
+    /// Reads the category icons from `CATEGORY_ICONS`. Favicons are used once
+    /// they have been fetched.
+    pub fn from_env() -> Icons {
+        let icons = Icons::parse_list(&env::var("CATEGORY_ICONS").unwrap_or_default());
+        match favicon::directory().filter(|directory| directory.is_dir()) {
+            Some(directory) => icons.with_favicons(directory),
+            None => icons,
+        }
+    }
+
+    /// Returns the icon of the category, or of the innermost parent that has
+    /// one.
+    pub fn category(&self, category: &str) -> Option<Icon> {
+        self.category_path(category).map(Icon::with_image)
+    }
+
+    fn category_path(&self, category: &str) -> Option<&str> {
+        self.categories
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.to_lowercase();
+                let category = category.to_lowercase();
+                category == name || category.starts_with(&format!("{}/", name))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, icon)| icon.as_str())
+    }
+
+    /// Returns the favicon fetched for the host of a link.
+    fn favicon(&self, link: &str) -> Option<String> {
+        let host = split_link(link).0.to_lowercase();
+        let path = favicon::path(self.favicons.as_ref()?, &host);
+        Some(path.to_str()?.to_owned()).filter(|_| path.is_file())
+    }
+
+    /// Gives a bookmark without its own icon the icon of its category, or
+    /// else the favicon of its host.
+    pub fn assign(&self, bookmark: Bookmark) -> Bookmark {
+        if bookmark.icon.is_some() {
+            return bookmark;
+        }
+        let icon = self
+            .category_path(&bookmark.category())
+            .map(str::to_owned)
+            .or_else(|| self.favicon(&bookmark.link));
+        match icon {
+            Some(icon) => bookmark.with_icon(icon),
+            None => bookmark,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::icon::Icons;
+    use crate::test_support::TempDir;
+    use crate::Bookmark;
+
+    #[test]
+    fn uses_the_icon_of_the_innermost_category() {
+        let icons = Icons::parse_list("work=icons/work.png; work/tickets = icons/tickets.png;;");
+
+        let jira = icons.assign(
+            Bookmark::new("Jira", "https://jira.test.blub")
+                .with_path(vec!["Work".to_owned(), "tickets".to_owned()]),
+        );
+        let wiki = icons.assign(
+            Bookmark::new("Wiki", "https://wiki.test.blub").with_path(vec!["work".to_owned()]),
+        );
+        let dashboard = icons.assign(Bookmark::new("Dashboard", "http://www.test.blub"));
+
+        assert_eq!(jira.icon.as_deref(), Some("icons/tickets.png"));
+        assert_eq!(wiki.icon.as_deref(), Some("icons/work.png"));
+        assert_eq!(dashboard.icon, None);
+    }
+
+    #[test]
+    fn keeps_the_icon_of_the_bookmark() {
+        let icons = Icons::parse_list("work=icons/work.png");
+        let bookmark = Bookmark::new("Jira", "https://jira.test.blub")
+            .with_path(vec!["work".to_owned()])
+            .with_icon("icons/jira.png");
+
+        assert_eq!(icons.assign(bookmark.clone()), bookmark);
+    }
+
+    #[test]
+    fn falls_back_to_fetched_favicons() {
+        let directory = TempDir::new("icons");
+        fs::write(directory.join("jira.test.blub.ico"), "icon").unwrap();
+        let icons = Icons::default().with_favicons(directory.path());
+
+        let jira = icons.assign(Bookmark::new("Jira", "https://jira.test.blub/browse"));
+        let wiki = icons.assign(Bookmark::new("Wiki", "https://wiki.test.blub"));
+
+        assert_eq!(
+            jira.icon,
+            Some(
+                directory
+                    .join("jira.test.blub.ico")
+                    .to_str()
+                    .unwrap()
+                    .to_owned()
+            )
+        );
+        assert_eq!(wiki.icon, None);
+    }
+}
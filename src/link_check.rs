@@ -0,0 +1,631 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use json::{object, JsonValue};
+
+use crate::error::Error;
+use crate::{add, sources, template};
+
+/// Exit codes of curl for failed TLS handshakes and invalid certificates.
+const TLS_EXIT_CODES: [i32; 14] = [35, 51, 53, 54, 58, 59, 60, 64, 66, 77, 80, 82, 83, 91];
+
+const DNS_EXIT_CODE: i32 = 6;
+
+const TIMEOUT_EXIT_CODE: i32 = 28;
+
+/// How links are requested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// How long a single request may take, in seconds.
+    pub timeout: u64,
+    /// How many requests run at the same time.
+    pub concurrency: usize,
+    /// How many requests are started per second at most.
+    pub rate: f64,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            timeout: 10,
+            concurrency: 8,
+            rate: 10.0,
+        }
+    }
+}
+
+/// What `check-links` does, parsed from its arguments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Arguments {
+    pub options: Options,
+    pub as_json: bool,
+    /// The file the report is written to instead of printing it.
+    pub report: Option<PathBuf>,
+    /// The file the checked bookmark file is written to with permanent
+    /// redirects replaced.
+    pub rewrite: Option<PathBuf>,
+    /// The bookmark files to check, those of `BOOKMARKS_FILE` if empty.
+    pub paths: Vec<PathBuf>,
+}
+
+impl Arguments {
+    pub fn parse(arguments: &[String]) -> Result<Arguments> {
+        let mut parsed = Arguments::default();
+        let mut arguments = arguments.iter();
+        while let Some(argument) = arguments.next() {
+            let mut value = || {
+                arguments
+                    .next()
+                    .context(format!("{} needs a value", argument))
+            };
+            match argument.as_str() {
+                "--json" => parsed.as_json = true,
+                "--timeout" => {
+                    let timeout = value()?.parse()?;
+                    if timeout == 0 {
+                        bail!("--timeout must be greater than 0");
+                    }
+                    parsed.options.timeout = timeout;
+                }
+                "--concurrency" => parsed.options.concurrency = value()?.parse()?,
+                "--rate" => {
+                    let rate: f64 = value()?.parse()?;
+                    if rate.is_nan() || rate <= 0.0 {
+                        bail!("--rate must be greater than 0");
+                    }
+                    // `check` waits 1 / rate seconds between requests.
+                    if Duration::try_from_secs_f64(1.0 / rate).is_err() {
+                        bail!("--rate is too small to wait between requests");
+                    }
+                    parsed.options.rate = rate;
+                }
+                "--report" => parsed.report = Some(PathBuf::from(value()?)),
+                "--rewrite" => parsed.rewrite = Some(PathBuf::from(value()?)),
+                option if option.starts_with("--") => bail!("unknown option {}", option),
+                path => parsed.paths.push(PathBuf::from(path)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// The result of requesting a link.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Status {
+    Ok(u16),
+    /// Redirected to the location, permanently for 301 and 308.
+    Redirect {
+        code: u16,
+        location: String,
+    },
+    ClientError(u16),
+    ServerError(u16),
+    /// The host could not be resolved.
+    Dns,
+    /// The TLS handshake failed, e.g. because of an invalid certificate.
+    Tls,
+    Timeout,
+    /// The request failed otherwise, e.g. because the connection was refused.
+    Failed(String),
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Ok(_) => "ok",
+            Status::Redirect { .. } => "redirect",
+            Status::ClientError(_) => "4xx",
+            Status::ServerError(_) => "5xx",
+            Status::Dns => "dns",
+            Status::Tls => "tls",
+            Status::Timeout => "timeout",
+            Status::Failed(_) => "failed",
+        }
+    }
+
+    pub fn code(&self) -> Option<u16> {
+        match self {
+            Status::Ok(code)
+            | Status::Redirect { code, .. }
+            | Status::ClientError(code)
+            | Status::ServerError(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Returns the new location of a permanent redirect.
+    pub fn permanent_location(&self) -> Option<&str> {
+        match self {
+            Status::Redirect { code, location } if *code == 301 || *code == 308 => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the link doesn't work anymore.
+    pub fn is_broken(&self) -> bool {
+        !matches!(self, Status::Ok(_) | Status::Redirect { .. })
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Status::Redirect { code, location } => format!("redirect {} to {}", code, location),
+            Status::Dns => String::from("dns: could not resolve the host"),
+            Status::Tls => String::from("tls: the secure connection failed"),
+            Status::Timeout => String::from("timeout"),
+            Status::Failed(message) => format!("failed: {}", message),
+            status => format!("{} {}", status.name(), status.code().unwrap_or_default()),
+        }
+    }
+}
+
+/// Requests the links of the bookmark files that can be requested, each one
+/// once with the titles of all bookmarks having it.
+pub fn check_files(paths: &[PathBuf], options: &Options) -> Result<Vec<Checked>, Error> {
+    let links = links(paths)?;
+    let statuses = check(
+        &links
+            .iter()
+            .map(|(link, _)| link.to_owned())
+            .collect::<Vec<_>>(),
+        options,
+    );
+    Ok(links
+        .into_iter()
+        .zip(statuses)
+        .map(|((link, titles), status)| Checked {
+            link,
+            titles,
+            status,
+        })
+        .collect())
+}
+
+/// Returns the http and https links of the bookmark files without
+/// placeholders, in the order they appear, with the titles of the bookmarks
+/// having them.
+fn links(paths: &[PathBuf]) -> Result<Vec<(String, Vec<String>)>, Error> {
+    let mut links: Vec<(String, Vec<String>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for path in paths {
+        let (bookmarks, _) = sources::read(path).map_err(|problem| Error::File {
+            path: path.to_owned(),
+            problem,
+        })?;
+        for bookmark in bookmarks {
+            for link in std::iter::once(&bookmark.link).chain(bookmark.links.iter()) {
+                if let Some(position) = positions.get(link) {
+                    links[*position].1.push(bookmark.name.to_owned());
+                } else if is_checkable(link) {
+                    positions.insert(link.to_owned(), links.len());
+                    links.push((link.to_owned(), vec![bookmark.name.to_owned()]));
+                }
+            }
+        }
+    }
+    Ok(links)
+}
+
+fn is_checkable(link: &str) -> bool {
+    (link.starts_with("http://") || link.starts_with("https://")) && !template::is_template(link)
+}
+
+/// Requests all links, at most `concurrency` at the same time and `rate` per
+/// second, and returns their status in the same order.
+pub fn check(links: &[String], options: &Options) -> Vec<Status> {
+    let interval = Duration::from_secs_f64(1.0 / options.rate);
+    let mut statuses: Vec<Option<Status>> = vec![None; links.len()];
+    let mut running: Vec<(usize, Child)> = Vec::new();
+    let mut next = 0;
+    let mut last_start: Option<Instant> = None;
+    while next < links.len() || !running.is_empty() {
+        let may_start = last_start.is_none_or(|start| start.elapsed() >= interval);
+        if next < links.len() && running.len() < options.concurrency.max(1) && may_start {
+            match request(&links[next], options.timeout) {
+                Ok(child) => running.push((next, child)),
+                Err(error) => statuses[next] = Some(Status::Failed(error.to_string())),
+            }
+            last_start = Some(Instant::now());
+            next += 1;
+            continue;
+        }
+        let mut index = 0;
+        while index < running.len() {
+            if let Ok(Some(_)) = running[index].1.try_wait() {
+                let (link, child) = running.swap_remove(index);
+                statuses[link] = Some(finish(child));
+            } else {
+                index += 1;
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    statuses
+        .into_iter()
+        .map(|status| status.unwrap_or_else(|| Status::Failed(String::from("not checked"))))
+        .collect()
+}
+
+/// Starts requesting a link without following redirects, printing the status
+/// code and the location redirected to.
+fn request(link: &str, timeout: u64) -> Result<Child> {
+    Ok(Command::new("curl")
+        .args(["--silent", "--output", "/dev/null"])
+        .args(["--write-out", "%{http_code} %{redirect_url}"])
+        .args(["--max-time", &timeout.to_string()])
+        .args([
+            "--user-agent",
+            "Mozilla/5.0 (Macintosh) bookmarks-alfred-workflow",
+        ])
+        .arg(link)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?)
+}
+
+fn finish(child: Child) -> Status {
+    match child.wait_with_output() {
+        Ok(output) => classify(
+            output.status.code(),
+            &String::from_utf8_lossy(&output.stdout),
+        ),
+        Err(error) => Status::Failed(error.to_string()),
+    }
+}
+
+/// Classifies the exit code of curl and its output of the status code and
+/// redirect location.
+fn classify(exit_code: Option<i32>, output: &str) -> Status {
+    match exit_code {
+        Some(0) => {}
+        Some(DNS_EXIT_CODE) => return Status::Dns,
+        Some(TIMEOUT_EXIT_CODE) => return Status::Timeout,
+        Some(code) if TLS_EXIT_CODES.contains(&code) => return Status::Tls,
+        Some(7) => return Status::Failed(String::from("could not connect")),
+        Some(code) => return Status::Failed(format!("curl exited with {}", code)),
+        None => return Status::Failed(String::from("curl was stopped")),
+    }
+    let (code, location) = output.split_once(' ').unwrap_or((output, ""));
+    match code.trim().parse::<u16>() {
+        Ok(code @ 300..=399) => Status::Redirect {
+            code,
+            location: location.trim().to_owned(),
+        },
+        Ok(code @ 400..=499) => Status::ClientError(code),
+        Ok(code @ 500..=599) => Status::ServerError(code),
+        Ok(code) if code > 0 => Status::Ok(code),
+        _ => Status::Failed(String::from("no http response")),
+    }
+}
+
+/// A checked link with the titles of its bookmarks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Checked {
+    pub link: String,
+    pub titles: Vec<String>,
+    pub status: Status,
+}
+
+/// Returns a line per link that isn't ok, followed by how many links had
+/// which status.
+pub fn report(checked: &[Checked]) -> String {
+    let mut lines: Vec<String> = checked
+        .iter()
+        .filter(|checked| !matches!(checked.status, Status::Ok(_)))
+        .map(|checked| {
+            format!(
+                "{}: {} ({})",
+                checked.link,
+                checked.status.describe(),
+                checked.titles.join(", ")
+            )
+        })
+        .collect();
+    let counts = checked.iter().map(|checked| checked.status.name()).counts();
+    let summary = [
+        "ok", "redirect", "4xx", "5xx", "dns", "tls", "timeout", "failed",
+    ]
+    .iter()
+    .filter_map(|name| Some(format!("{} {}", counts.get(name)?, name)))
+    .join(", ");
+    lines.push(format!("checked {} links: {}", checked.len(), summary));
+    lines.join("\n")
+}
+
+pub fn to_json(checked: &[Checked]) -> JsonValue {
+    let links: Vec<JsonValue> = checked
+        .iter()
+        .map(|checked| {
+            let location = match &checked.status {
+                Status::Redirect { location, .. } => Some(location.to_owned()),
+                _ => None,
+            };
+            object! {
+                link: checked.link.clone(),
+                titles: checked.titles.clone(),
+                status: checked.status.name(),
+                code: checked.status.code(),
+                location: location,
+            }
+        })
+        .collect();
+    object! {
+        checked: checked.len(),
+        broken: checked.iter().filter(|checked| checked.status.is_broken()).count(),
+        links: links,
+    }
+}
+
+/// Replaces the links that are permanently redirected in the contents of a
+/// json bookmark file, keeping its formatting. Only `href` and `hrefs` values
+/// are replaced. Returns the new contents and how many links were replaced.
+pub fn rewrite(contents: &str, checked: &[Checked]) -> Result<(String, usize)> {
+    let locations: HashMap<&str, &str> = checked
+        .iter()
+        .filter_map(|checked| Some((checked.link.as_str(), checked.status.permanent_location()?)))
+        .collect();
+    let spans = add::link_spans(contents).context("not a json object of categories")?;
+    let mut rewritten = contents.to_owned();
+    let mut count = 0;
+    // From the end, so that replacing doesn't move the spans still to come.
+    for span in spans.iter().rev() {
+        let link = json::parse(&contents[span.start..span.end])?;
+        if let Some(location) = link.as_str().and_then(|link| locations.get(link)) {
+            rewritten.replace_range(span.start..span.end, &json::stringify(*location));
+            count += 1;
+        }
+    }
+    Ok((rewritten, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::link_check::{
+        check, classify, links, report, rewrite, Arguments, Checked, Options, Status,
+    };
+    use crate::test_support::{closed_port, serve, TempDir};
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let parsed = Arguments::parse(&arguments(&[
+            "--json",
+            "--rate",
+            "2.5",
+            "--report",
+            "report.json",
+            "team.json",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            Arguments {
+                options: Options {
+                    rate: 2.5,
+                    ..Options::default()
+                },
+                as_json: true,
+                report: Some(PathBuf::from("report.json")),
+                rewrite: None,
+                paths: vec![PathBuf::from("team.json")],
+            }
+        );
+        assert!(Arguments::parse(&arguments(&["--rate", "0"])).is_err());
+        assert!(Arguments::parse(&arguments(&["--rate", "-1"])).is_err());
+        assert!(Arguments::parse(&arguments(&["--rate", "1e-20"])).is_err());
+        assert!(Arguments::parse(&arguments(&["--timeout", "0"])).is_err());
+        assert!(Arguments::parse(&arguments(&["--timeout"])).is_err());
+        assert!(Arguments::parse(&arguments(&["--colour"])).is_err());
+    }
+
+    #[test]
+    fn collects_each_link_once_with_all_titles() {
+        let directory = TempDir::new("link-check");
+        let team = directory.join("team.json");
+        let personal = directory.join("personal.json");
+        fs::write(
+            &team,
+            r#"{"work": [
+                {"href": "https://jira.test.blub", "title": "Jira"},
+                {"href": "https://jira.test.blub/browse/{query}", "title": "Ticket"},
+                {"href": "mailto:team@test.blub", "title": "Team"}
+            ]}"#,
+        )
+        .unwrap();
+        fs::write(
+            &personal,
+            r#"{"private": [
+                {"href": "http://www.test.blub", "title": "Dashboard"},
+                {"href": "https://jira.test.blub", "title": "My Jira"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let links = links(&[team, personal]).unwrap();
+
+        assert_eq!(
+            links,
+            vec![
+                (
+                    "https://jira.test.blub".to_owned(),
+                    vec!["Jira".to_owned(), "My Jira".to_owned()]
+                ),
+                (
+                    "http://www.test.blub".to_owned(),
+                    vec!["Dashboard".to_owned()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_failures() {
+        assert_eq!(classify(Some(6), "000 "), Status::Dns);
+        assert_eq!(classify(Some(60), "000 "), Status::Tls);
+        assert_eq!(classify(Some(28), "000 "), Status::Timeout);
+        assert_eq!(
+            classify(Some(7), "000 "),
+            Status::Failed("could not connect".to_owned())
+        );
+        assert_eq!(classify(Some(0), "204 "), Status::Ok(204));
+    }
+
+    /// Serves a status per path, redirecting `/moved` permanently and
+    /// `/elsewhere` temporarily, and answering `/slow` after two seconds.
+    fn serve_statuses() -> u16 {
+        serve(|path| {
+            let status = match path {
+                "/moved" => "301 Moved Permanently\r\nLocation: /ok",
+                "/elsewhere" => "302 Found\r\nLocation: /ok",
+                "/missing" => "404 Not Found",
+                "/broken" => "500 Internal Server Error",
+                "/slow" => {
+                    thread::sleep(Duration::from_secs(2));
+                    "200 OK"
+                }
+                _ => "200 OK",
+            };
+            (String::from(status), String::new())
+        })
+    }
+
+    #[test]
+    fn checks_links_against_a_server() {
+        let port = serve_statuses();
+        let closed_port = closed_port();
+        let url = |path: &str| format!("http://127.0.0.1:{}{}", port, path);
+        let links = vec![
+            url("/ok"),
+            url("/moved"),
+            url("/elsewhere"),
+            url("/missing"),
+            url("/broken"),
+            url("/slow"),
+            format!("http://127.0.0.1:{}/", closed_port),
+        ];
+        let options = Options {
+            timeout: 1,
+            concurrency: 4,
+            rate: 100.0,
+        };
+
+        let statuses = check(&links, &options);
+
+        assert_eq!(
+            statuses,
+            vec![
+                Status::Ok(200),
+                Status::Redirect {
+                    code: 301,
+                    location: url("/ok")
+                },
+                Status::Redirect {
+                    code: 302,
+                    location: url("/ok")
+                },
+                Status::ClientError(404),
+                Status::ServerError(500),
+                Status::Timeout,
+                Status::Failed("could not connect".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_links_that_are_not_ok() {
+        let checked = vec![
+            Checked {
+                link: "https://jira.test.blub".to_owned(),
+                titles: vec!["Jira".to_owned()],
+                status: Status::Ok(200),
+            },
+            Checked {
+                link: "https://wiki.test.blub/old".to_owned(),
+                titles: vec!["Wiki".to_owned(), "Docs".to_owned()],
+                status: Status::ClientError(404),
+            },
+        ];
+
+        assert_eq!(
+            report(&checked),
+            "https://wiki.test.blub/old: 4xx 404 (Wiki, Docs)\n\
+             checked 2 links: 1 ok, 1 4xx"
+        );
+    }
+
+    #[test]
+    fn applies_permanent_redirects() {
+        let contents = "{\n  \"work\": [\n    {\"title\": \"Wiki\", \"href\": \"http://wiki.test.blub\"},\n    {\"title\": \"Jira\", \"href\": \"http://jira.test.blub\"}\n  ]\n}\n";
+        let checked = vec![
+            Checked {
+                link: "http://wiki.test.blub".to_owned(),
+                titles: vec!["Wiki".to_owned()],
+                status: Status::Redirect {
+                    code: 301,
+                    location: "https://wiki.test.blub/".to_owned(),
+                },
+            },
+            Checked {
+                link: "http://jira.test.blub".to_owned(),
+                titles: vec!["Jira".to_owned()],
+                status: Status::Redirect {
+                    code: 302,
+                    location: "http://jira.test.blub/login".to_owned(),
+                },
+            },
+        ];
+
+        let (rewritten, count) = rewrite(contents, &checked).unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(
+            rewritten,
+            contents.replace("\"http://wiki.test.blub\"", "\"https://wiki.test.blub/\"")
+        );
+    }
+
+    #[test]
+    fn rewrites_only_links() {
+        let contents = r#"{"work": [
+            {"title": "http://wiki.test.blub", "href": "http://wiki.test.blub", "icon": "http://wiki.test.blub"},
+            {"title": "Morning", "hrefs": ["http://jira.test.blub", "http://wiki.test.blub"]}
+        ]}"#;
+        let checked = vec![Checked {
+            link: "http://wiki.test.blub".to_owned(),
+            titles: vec!["http://wiki.test.blub".to_owned(), "Morning".to_owned()],
+            status: Status::Redirect {
+                code: 308,
+                location: "https://wiki.test.blub/".to_owned(),
+            },
+        }];
+
+        let (rewritten, count) = rewrite(contents, &checked).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            rewritten,
+            r#"{"work": [
+            {"title": "http://wiki.test.blub", "href": "https://wiki.test.blub/", "icon": "http://wiki.test.blub"},
+            {"title": "Morning", "hrefs": ["http://jira.test.blub", "https://wiki.test.blub/"]}
+        ]}"#
+        );
+        assert!(rewrite("[]", &checked).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use json::{object, JsonValue};
+
+use crate::error::Problem;
+use crate::field::split_link;
+use crate::sources::{self, chromium};
+use crate::{template, Bookmark};
+
+/// The schemes of links that can be opened.
+const SUPPORTED_SCHEMES: [&str; 5] = ["http", "https", "ftp", "file", "mailto"];
+
+/// The checks run on bookmark files.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Check {
+    /// The file can't be read or an entry misses fields.
+    Schema,
+    DuplicateUrl,
+    /// Two bookmarks of a category have the same title.
+    DuplicateTitle,
+    MalformedUrl,
+    UnsupportedScheme,
+    EmptyCategory,
+}
+
+impl Check {
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Schema => "schema",
+            Check::DuplicateUrl => "duplicate-url",
+            Check::DuplicateTitle => "duplicate-title",
+            Check::MalformedUrl => "malformed-url",
+            Check::UnsupportedScheme => "unsupported-scheme",
+            Check::EmptyCategory => "empty-category",
+        }
+    }
+}
+
+/// A problem found in a bookmark file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Issue {
+    pub check: Check,
+    /// The category of the entry, or `None` for the whole file.
+    pub category: Option<String>,
+    /// The position of the entry in its category, if known.
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(check: Check, category: Option<&str>, index: Option<usize>, message: String) -> Issue {
+        Issue {
+            check,
+            category: category.map(str::to_owned),
+            index,
+            message,
+        }
+    }
+
+    /// Describes where the issue is, e.g. `entry 2 of "work"`.
+    pub fn location(&self) -> String {
+        describe(self.category.as_deref(), self.index)
+    }
+}
+
+impl From<Problem> for Issue {
+    fn from(problem: Problem) -> Issue {
+        match problem {
+            Problem::InvalidEntry {
+                category,
+                index,
+                message,
+            } => Issue::new(Check::Schema, Some(&category), index, message),
+            problem => Issue::new(Check::Schema, None, None, problem.to_string()),
+        }
+    }
+}
+
+fn describe(category: Option<&str>, index: Option<usize>) -> String {
+    match (category, index) {
+        (Some(category), Some(index)) => format!("entry {} of \"{}\"", index + 1, category),
+        (Some(""), None) => String::from("the top level"),
+        (Some(category), None) => format!("\"{}\"", category),
+        (None, _) => String::from("the file"),
+    }
+}
+
+/// A bookmark with its position in the file.
+struct Entry {
+    category: String,
+    index: Option<usize>,
+    bookmark: Bookmark,
+}
+
+/// Checks a bookmark file. Entries of the json format are reported with
+/// their category and index, bookmarks of other formats with their folder.
+pub fn lint(path: &Path) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let entries = match fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| json_format(&contents))
+    {
+        Some(parsed) => json_entries(&parsed, &mut issues),
+        None => match sources::read(path) {
+            Ok((bookmarks, skipped)) => {
+                issues.extend(skipped.into_iter().map(Issue::from));
+                bookmarks
+                    .into_iter()
+                    .map(|bookmark| Entry {
+                        category: bookmark.category(),
+                        index: None,
+                        bookmark,
+                    })
+                    .collect()
+            }
+            Err(problem) => return vec![Issue::from(problem)],
+        },
+    };
+
+    let mut links: HashMap<String, &Entry> = HashMap::new();
+    let mut titles: HashMap<(&str, String), &Entry> = HashMap::new();
+    for entry in &entries {
+        let issue = |check, message| Issue::new(check, Some(&entry.category), entry.index, message);
+        let mut entry_links: Vec<&String> = std::iter::once(&entry.bookmark.link)
+            .chain(entry.bookmark.links.iter())
+            .collect();
+        entry_links.dedup();
+        for link in entry_links {
+            if let Some((check, message)) = check_link(link) {
+                issues.push(issue(check, message));
+            }
+            if let Some(first) = links.get(&normalize(link)) {
+                let message = format!(
+                    "{} is also {}",
+                    link,
+                    describe(Some(&first.category), first.index)
+                );
+                issues.push(issue(Check::DuplicateUrl, message));
+            } else {
+                links.insert(normalize(link), entry);
+            }
+        }
+        let title = (entry.category.as_str(), entry.bookmark.name.to_lowercase());
+        if let Some(first) = titles.get(&title) {
+            let message = format!(
+                "title \"{}\" is also used by {}",
+                entry.bookmark.name,
+                describe(Some(&first.category), first.index)
+            );
+            issues.push(issue(Check::DuplicateTitle, message));
+        } else {
+            titles.insert(title, entry);
+        }
+    }
+    issues
+}
+
+/// Returns the parsed file if it has the json format of the workflow.
+pub fn json_format(contents: &str) -> Option<JsonValue> {
+    json::parse(contents)
+        .ok()
+        .filter(|parsed| parsed.is_object() && !chromium::is_chromium(parsed))
+}
+
+/// Returns the valid entries of the json format, adding the invalid ones and
+/// empty categories to the issues.
+fn json_entries(parsed: &JsonValue, issues: &mut Vec<Issue>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for (category, members) in parsed.entries() {
+        if !members.is_array() {
+            let message = String::from("is not a list of bookmarks");
+            issues.push(Issue::new(Check::Schema, Some(category), None, message));
+        } else if members.is_empty() {
+            let message = String::from("has no bookmarks");
+            issues.push(Issue::new(
+                Check::EmptyCategory,
+                Some(category),
+                None,
+                message,
+            ));
+        }
+        for (index, member) in members.members().enumerate() {
+            match Bookmark::from_json_value(member) {
+                Ok(bookmark) => entries.push(Entry {
+                    category: category.to_owned(),
+                    index: Some(index),
+                    bookmark,
+                }),
+                Err(message) => issues.push(Issue::new(
+                    Check::Schema,
+                    Some(category),
+                    Some(index),
+                    message,
+                )),
+            }
+        }
+    }
+    entries
+}
+
+/// Returns what is wrong with a link, if anything. Placeholders count as
+/// valid parts of the link.
+pub fn check_link(link: &str) -> Option<(Check, String)> {
+    let filled = template::fill(link, &[String::from("x")]);
+    if filled.contains(char::is_whitespace) {
+        return Some((Check::MalformedUrl, format!("{} contains whitespace", link)));
+    }
+    let scheme = filled
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        });
+    let scheme = match scheme {
+        Some(scheme) => scheme.to_lowercase(),
+        None => return Some((Check::MalformedUrl, format!("{} has no scheme", link))),
+    };
+    if !SUPPORTED_SCHEMES.contains(&scheme.as_str()) {
+        let message = format!("{} uses the unsupported scheme {}", link, scheme);
+        return Some((Check::UnsupportedScheme, message));
+    }
+    if ["http", "https", "ftp"].contains(&scheme.as_str()) {
+        let (host, _) = split_link(&filled);
+        let valid_host = !host.is_empty()
+            && filled[scheme.len()..].starts_with("://")
+            && host
+                .chars()
+                .all(|c| c.is_alphanumeric() || "-.:[]_".contains(c));
+        if !valid_host {
+            return Some((Check::MalformedUrl, format!("{} has no valid host", link)));
+        }
+    }
+    None
+}
+
+/// Returns the link with its scheme and host lowercased and without a
+/// trailing slash, to find the same link written differently.
+pub fn normalize(link: &str) -> String {
+    match link.split_once("://") {
+        Some((scheme, _)) => {
+            let (host, path) = split_link(link);
+            format!(
+                "{}://{}{}",
+                scheme.to_lowercase(),
+                host.to_lowercase(),
+                path.trim_end_matches('/')
+            )
+        }
+        None => link.to_owned(),
+    }
+}
+
+/// Returns a line per issue like `bookmarks.json: entry 2 of "work":
+/// duplicate-url: ...` followed by a summary.
+pub fn report(files: &[(PathBuf, Vec<Issue>)]) -> String {
+    let mut lines: Vec<String> = files
+        .iter()
+        .flat_map(|(path, issues)| {
+            issues.iter().map(move |issue| {
+                format!(
+                    "{}: {}: {}: {}",
+                    path.display(),
+                    issue.location(),
+                    issue.check.name(),
+                    issue.message
+                )
+            })
+        })
+        .collect();
+    let count: usize = files.iter().map(|(_, issues)| issues.len()).sum();
+    let files = match files.len() {
+        1 => String::from("1 file"),
+        count => format!("{} files", count),
+    };
+    lines.push(match count {
+        0 => format!("no problems found in {}", files),
+        1 => format!("1 problem found in {}", files),
+        count => format!("{} problems found in {}", count, files),
+    });
+    lines.join("\n")
+}
+
+/// Returns the issues as json, with the index of entries counted from 0.
+pub fn to_json(files: &[(PathBuf, Vec<Issue>)]) -> JsonValue {
+    let mut issues = JsonValue::new_array();
+    for (path, file_issues) in files {
+        for issue in file_issues {
+            let _ = issues.push(object! {
+                file: path.display().to_string(),
+                check: issue.check.name(),
+                category: issue.category.clone(),
+                index: issue.index,
+                message: issue.message.clone(),
+            });
+        }
+    }
+    object! {
+        files: files.iter().map(|(path, _)| path.display().to_string()).collect::<Vec<_>>(),
+        count: issues.len(),
+        issues: issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::lint::{check_link, lint, report, to_json, Check, Issue};
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn checks_links() {
+        assert_eq!(check_link("https://jira.test.blub/browse/{query}"), None);
+        assert_eq!(check_link("mailto:ops@test.blub"), None);
+        assert_eq!(
+            check_link("jira.test.blub"),
+            Some((
+                Check::MalformedUrl,
+                "jira.test.blub has no scheme".to_owned()
+            ))
+        );
+        assert_eq!(
+            check_link("https://"),
+            Some((Check::MalformedUrl, "https:// has no valid host".to_owned()))
+        );
+        assert_eq!(
+            check_link("https://jira test.blub"),
+            Some((
+                Check::MalformedUrl,
+                "https://jira test.blub contains whitespace".to_owned()
+            ))
+        );
+        assert_eq!(
+            check_link("javascript:alert(1)"),
+            Some((
+                Check::UnsupportedScheme,
+                "javascript:alert(1) uses the unsupported scheme javascript".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn lints_the_json_format() {
+        let directory = TempDir::new("lint");
+        let path = directory.join("bookmarks.json");
+        fs::write(
+            &path,
+            r#"{
+                "work": [
+                    {"title": "Jira", "href": "https://jira.test.blub/"},
+                    {"title": "jira", "href": "https://JIRA.test.blub"},
+                    {"title": "Wiki"}
+                ],
+                "private": [],
+                "tools": [{"title": "Bookmarklet", "href": "javascript:void(0)"}]
+            }"#,
+        )
+        .unwrap();
+        let expected_issues = vec![
+            Issue {
+                check: Check::Schema,
+                category: Some("work".to_owned()),
+                index: Some(2),
+                message: "missing href".to_owned(),
+            },
+            Issue {
+                check: Check::EmptyCategory,
+                category: Some("private".to_owned()),
+                index: None,
+                message: "has no bookmarks".to_owned(),
+            },
+            Issue {
+                check: Check::DuplicateUrl,
+                category: Some("work".to_owned()),
+                index: Some(1),
+                message: "https://JIRA.test.blub is also entry 1 of \"work\"".to_owned(),
+            },
+            Issue {
+                check: Check::DuplicateTitle,
+                category: Some("work".to_owned()),
+                index: Some(1),
+                message: "title \"jira\" is also used by entry 1 of \"work\"".to_owned(),
+            },
+            Issue {
+                check: Check::UnsupportedScheme,
+                category: Some("tools".to_owned()),
+                index: Some(0),
+                message: "javascript:void(0) uses the unsupported scheme javascript".to_owned(),
+            },
+        ];
+
+        let issues = lint(&path);
+
+        assert_eq!(issues, expected_issues);
+    }
+
+    #[test]
+    fn reports_invalid_json() {
+        let directory = TempDir::new("lint-invalid");
+        let path = directory.join("bookmarks.json");
+        fs::write(&path, "{\"work\": [}").unwrap();
+
+        let issues = lint(&path);
+
+        assert_eq!(
+            issues,
+            vec![Issue {
+                check: Check::Schema,
+                category: None,
+                index: None,
+                message: "line 1, column 11: unexpected character }".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_readable_and_json_output() {
+        let files = vec![(
+            PathBuf::from("bookmarks.json"),
+            vec![Issue {
+                check: Check::MalformedUrl,
+                category: Some("work".to_owned()),
+                index: Some(0),
+                message: "jira has no scheme".to_owned(),
+            }],
+        )];
+
+        assert_eq!(
+            report(&files),
+            "bookmarks.json: entry 1 of \"work\": malformed-url: jira has no scheme\n\
+             1 problem found in 1 file"
+        );
+        assert_eq!(
+            to_json(&files).dump(),
+            r#"{"files":["bookmarks.json"],"count":1,"issues":[{"file":"bookmarks.json","check":"malformed-url","category":"work","index":0,"message":"jira has no scheme"}]}"#
+        );
+    }
+}
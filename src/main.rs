@@ -2,63 +2,347 @@ extern crate json;
 
 use std::env;
 use std::fs;
+use std::io::Write;
 use std::ops::Neg;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use itertools::Itertools;
 use json::JsonValue;
-use powerpack::Item;
+use powerpack::{Icon, Item};
+
+mod action;
+mod add;
+mod error;
+mod favicon;
+mod field;
+mod history;
+mod icon;
+mod link_check;
+mod lint;
+mod query;
+mod search_engine;
+mod sources;
+mod template;
+#[cfg(test)]
+mod test_support;
+
+use action::{Action, Actions};
+use error::{Error, Problem};
+use field::Field;
+use history::History;
+use icon::Icons;
+use query::Query;
+use search_engine::SearchEngine;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Bookmark {
     name: String,
     link: String,
+    /// The folders containing the bookmark, outermost first.
+    path: Vec<String>,
+    /// When the bookmark was added, in seconds since the unix epoch.
+    added: Option<u64>,
+    tags: Vec<String>,
+    /// Exact queries that always show the bookmark first, e.g. `pr`.
+    keywords: Vec<String>,
+    /// A note about the bookmark, e.g. from the `<DD>` of a bookmark export.
+    description: Option<String>,
+    /// The file the bookmark was read from.
+    source: Option<String>,
+    /// The words typed to fill the placeholders of the link.
+    arguments: Vec<String>,
+    /// Further links of a group, opened together with `link`.
+    links: Vec<String>,
+    /// The path of the image shown for the bookmark.
+    icon: Option<String>,
 }
 
 impl Bookmark {
-    pub fn from_json_value(value: &JsonValue) -> Bookmark {
-        let name = value["title"].as_str().unwrap().to_owned();
-        let link = value["href"].as_str().unwrap().to_owned();
+    pub fn new(name: impl Into<String>, link: impl Into<String>) -> Bookmark {
         Bookmark {
-            name: name,
-            link: link,
+            name: name.into(),
+            link: link.into(),
+            path: Vec::new(),
+            added: None,
+            tags: Vec::new(),
+            keywords: Vec::new(),
+            description: None,
+            source: None,
+            arguments: Vec::new(),
+            links: Vec::new(),
+            icon: None,
         }
     }
 
-    pub fn to_item(&self) -> Item {
-        Item::new(self.name.to_string())
-            .subtitle("Open in browser →")
-            .arg(self.link.to_owned())
+    pub fn with_path(mut self, path: Vec<String>) -> Bookmark {
+        self.path = path;
+        self
+    }
+
+    pub fn with_added(mut self, added: u64) -> Bookmark {
+        self.added = Some(added);
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Bookmark {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_keywords(mut self, keywords: Vec<String>) -> Bookmark {
+        self.keywords = keywords;
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Bookmark {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Bookmark {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn with_arguments(mut self, arguments: Vec<String>) -> Bookmark {
+        self.arguments = arguments;
+        self
+    }
+
+    pub fn with_links(mut self, links: Vec<String>) -> Bookmark {
+        self.links = links;
+        self
+    }
+
+    pub fn with_icon(mut self, icon: impl Into<String>) -> Bookmark {
+        self.icon = Some(icon.into());
+        self
+    }
+
+    /// Reads a bookmark with an `href`, or a group opening all of its `hrefs`.
+    /// Returns what is wrong with the entry if it can't be read.
+    pub fn from_json_value(value: &JsonValue) -> Result<Bookmark, String> {
+        if !value.is_object() {
+            return Err(String::from("is not an object"));
+        }
+        let name = optional_str(value, "title")?.ok_or("missing title")?;
+        let mut links: Vec<String> = optional_str(value, "href")?
+            .into_iter()
+            .map(str::to_owned)
+            .chain(string_list(value, "hrefs")?)
+            .collect();
+        if links.is_empty() {
+            return Err(String::from("missing href"));
+        }
+        let link = links.remove(0);
+        let keywords = optional_str(value, "keyword")?
+            .into_iter()
+            .map(str::to_owned)
+            .chain(string_list(value, "aliases")?)
+            .collect();
+        let bookmark = Bookmark::new(name, link)
+            .with_tags(string_list(value, "tags")?)
+            .with_keywords(keywords)
+            .with_links(links);
+        Ok(match optional_str(value, "icon")? {
+            Some(icon) => bookmark.with_icon(icon),
+            None => bookmark,
+        })
     }
 
-    pub fn calculate_matching_score(&self, query: String) -> i64 {
+    /// Returns true if the query is exactly one of the keywords.
+    pub fn has_keyword(&self, query: &str) -> bool {
+        self.keywords
+            .iter()
+            .any(|keyword| keyword.eq_ignore_ascii_case(query))
+    }
+
+    /// Returns the folder path joined by `/`, e.g. `work/tickets`.
+    pub fn category(&self) -> String {
+        self.path.join("/")
+    }
+
+    pub fn to_item(&self, actions: &Actions) -> Item {
+        let mut details = Vec::new();
+        if !self.path.is_empty() {
+            details.push(self.category());
+        }
+        if !self.tags.is_empty() {
+            details.push(self.tags.iter().map(|tag| format!("#{}", tag)).join(" "));
+        }
+        if !self.links.is_empty() {
+            details.push(format!("Open {} links →", self.links.len() + 1));
+        } else if template::is_template(&self.link) {
+            details.push(format!("Open {} →", self.url()));
+        } else {
+            details.push(String::from("Open in browser →"));
+        }
+        let subtitle = details.join(" · ");
+        let urls = self.urls().join("\n");
+        let mut item = Item::new(self.name.to_string())
+            .subtitle(subtitle)
+            .uid(self.uid())
+            .arg(urls.to_owned())
+            .quicklook_url(self.url())
+            .copy_text(urls.to_owned())
+            .large_type_text(format!("{}\n{}", self.name, urls));
+        if let Some(icon) = &self.icon {
+            item = item.icon(Icon::with_image(icon));
+        }
+        actions
+            .modifiers(self)
+            .into_iter()
+            .fold(item, |item, modifier| item.modifier(modifier))
+    }
+
+    /// Returns an id that stays the same as long as the link and source do,
+    /// so Alfred can learn which results are chosen. Links with placeholders
+    /// keep their id whatever is typed for them.
+    pub fn uid(&self) -> String {
+        let key = format!(
+            "{}\n{}",
+            self.source.as_deref().unwrap_or_default(),
+            self.link
+        );
+        // FNV-1a, as the std hasher is not guaranteed to be stable.
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    /// Returns the link with its placeholders filled with the arguments.
+    pub fn url(&self) -> String {
+        fill_arguments(&self.link, &self.arguments)
+    }
+
+    /// Returns all links to open, more than one for groups.
+    pub fn urls(&self) -> Vec<String> {
+        std::iter::once(&self.link)
+            .chain(self.links.iter())
+            .map(|link| fill_arguments(link, &self.arguments))
+            .collect()
+    }
+
+    /// Matches the query and returns the bookmark with its arguments, whether
+    /// the query is a keyword and the matching score. For links with
+    /// placeholders the first word selects the bookmark and the other words
+    /// are the arguments, e.g. "jira ABC-123".
+    pub fn match_query(&self, query: &str, fields: &[Field]) -> (Bookmark, bool, i64) {
+        if template::is_template(&self.link) {
+            if let Some((first, arguments)) = query.trim().split_once(char::is_whitespace) {
+                let is_keyword = self.has_keyword(first);
+                let score = self.calculate_matching_score(first.to_owned(), fields);
+                if is_keyword || score < 0 {
+                    let arguments = arguments.split_whitespace().map(str::to_owned).collect();
+                    return (self.clone().with_arguments(arguments), is_keyword, score);
+                }
+            }
+        }
+        let is_keyword = self.has_keyword(query);
+        let score = self.calculate_matching_score(query.to_owned(), fields);
+        (self.clone(), is_keyword, score)
+    }
+
+    /// Matches each word of the query against the fields in any order and
+    /// sums up the best weighted score per word, negated so that better
+    /// matches sort first. If any word does not match, the score is 0.
+    pub fn calculate_matching_score(&self, query: String, fields: &[Field]) -> i64 {
         let matcher = SkimMatcherV2::default();
-        return matcher
-            .fuzzy_match(&self.name[..], &query[..])
+        let values: Vec<(Field, String)> = fields
+            .iter()
+            .map(|field| (*field, field.value(self)))
+            .collect();
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .map(|token| {
+                values
+                    .iter()
+                    .filter_map(|(field, value)| {
+                        let score = matcher.fuzzy_match(&value[..], token)?;
+                        Some(score * field.weight() / 100)
+                    })
+                    .max()
+            })
+            .sum::<Option<i64>>()
             .get_or_insert(0)
             .to_owned()
-            .neg();
+            .neg()
     }
 }
 
-pub fn read_bookmarks(json: String) -> Vec<Bookmark> {
-    let parsed = json::parse(&json).unwrap();
-    let json_arrays = parsed
-        .entries()
-        .map(|entry| entry.1)
-        .collect::<Vec<&JsonValue>>();
+/// Returns the string value of a field, or an error if it is no string.
+fn optional_str<'a>(value: &'a JsonValue, key: &str) -> Result<Option<&'a str>, String> {
+    match &value[key] {
+        JsonValue::Null => Ok(None),
+        field => field
+            .as_str()
+            .map(Some)
+            .ok_or_else(|| format!("{} is not a string", key)),
+    }
+}
 
-    return json_arrays
-        .into_iter()
-        .map(|entry| {
-            entry
-                .members()
-                .map(|entry| Bookmark::from_json_value(entry))
-        })
-        .flatten()
-        .collect();
+/// Returns the strings of a list field, or an error if it is no list of
+/// strings.
+fn string_list(value: &JsonValue, key: &str) -> Result<Vec<String>, String> {
+    match &value[key] {
+        JsonValue::Null => Ok(Vec::new()),
+        JsonValue::Array(members) => members
+            .iter()
+            .map(|member| member.as_str().map(str::to_owned))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("{} is not a list of strings", key)),
+        _ => Err(format!("{} is not a list of strings", key)),
+    }
+}
+
+fn fill_arguments(link: &str, arguments: &[String]) -> String {
+    if template::is_template(link) {
+        template::fill(link, arguments)
+    } else {
+        link.to_owned()
+    }
+}
+
+/// Reads the bookmarks of the json format. Invalid entries are skipped and
+/// returned with what is wrong with them.
+pub fn read_bookmarks(json: String) -> Result<(Vec<Bookmark>, Vec<Problem>), Problem> {
+    let parsed = json::parse(&json)?;
+    if !parsed.is_object() {
+        return Err(Problem::InvalidJson {
+            position: None,
+            message: String::from("expected an object of categories"),
+        });
+    }
+
+    let mut bookmarks = Vec::new();
+    let mut skipped = Vec::new();
+    for (category, entries) in parsed.entries() {
+        if !entries.is_array() {
+            skipped.push(Problem::InvalidEntry {
+                category: category.to_owned(),
+                index: None,
+                message: String::from("is not a list of bookmarks"),
+            });
+            continue;
+        }
+        for (index, entry) in entries.members().enumerate() {
+            match Bookmark::from_json_value(entry) {
+                Ok(bookmark) => bookmarks.push(bookmark.with_path(vec![category.to_owned()])),
+                Err(message) => skipped.push(Problem::InvalidEntry {
+                    category: category.to_owned(),
+                    index: Some(index),
+                    message,
+                }),
+            }
+        }
+    }
+    Ok((bookmarks, skipped))
 }
 
 /// Returns an Alfred item for when no query has been typed yet.
@@ -69,135 +353,1323 @@ fn empty(default_search_url: String) -> Item {
 }
 
 /// Returns an Alfred item for when the query doesn't match any crates.
-fn default(query: String, default_search_url: String) -> Item {
-    Item::new(format!(
-        "nothing found for {}, try search on website",
-        query
-    ))
-    .subtitle("Open them →")
-    .arg(default_search_url)
+fn default(query: String, search_engine: &SearchEngine) -> Item {
+    let title = match &search_engine.name {
+        Some(name) => format!("nothing found for {}, try search on {}", query, name),
+        None => format!("nothing found for {}, try search on website", query),
+    };
+    Item::new(title)
+        .subtitle("Open them →")
+        .arg(search_engine.url_for(&query))
+}
+
+/// Returns an Alfred item for a category, with its icon if it has one.
+fn folder_item(category: &str, icons: &Icons) -> Item {
+    let item = Item::new(category.to_owned());
+    match icons.category(category) {
+        Some(icon) => item.icon(icon),
+        None => item,
+    }
 }
 
-fn sort_and_filter_matching_bookmarks(bookmarks: Vec<Bookmark>, query: String) -> Vec<Bookmark> {
-    return bookmarks
+/// Returns Alfred items completing the partially typed category of `@wo`,
+/// regardless of case.
+fn category_items(bookmarks: &[Bookmark], partial: &str, icons: &Icons) -> Vec<Item> {
+    let matcher = SkimMatcherV2::default();
+    let partial = partial.to_lowercase();
+    bookmarks
         .iter()
-        .sorted_by_key(|bookmark| bookmark.calculate_matching_score(query.to_owned()))
-        .filter(|bookmark| bookmark.calculate_matching_score(query.to_owned()) < 0)
-        .map(|bookmark| bookmark.to_owned())
-        .collect();
+        .flat_map(|bookmark| {
+            (1..=bookmark.path.len()).map(move |depth| bookmark.path[..depth].join("/"))
+        })
+        .unique()
+        .filter_map(|category| Some((matcher.fuzzy_match(&category, &partial)?, category)))
+        .sorted_by_key(|(score, _)| score.neg())
+        .map(|(_, category)| {
+            folder_item(&category, icons)
+                .subtitle("Search in category →")
+                .autocomplete(query::scope(&category))
+                .valid(false)
+        })
+        .collect()
 }
 
-fn to_items(bookmarks: Vec<Bookmark>, query: String, default_search_url: String) -> Vec<Item> {
-    let matched_bookmarks: Vec<Item> = sort_and_filter_matching_bookmarks(bookmarks, query.clone())
+/// Returns Alfred items to browse a category: its subfolders, which drill
+/// down further when autocompleted, followed by its bookmarks. Without a
+/// category the top level is browsed.
+fn browse_items(
+    bookmarks: &[Bookmark],
+    category: Option<&str>,
+    actions: &Actions,
+    icons: &Icons,
+) -> Vec<Item> {
+    let depth = category.map_or(0, |category| category.split('/').count());
+    let in_category: Vec<&Bookmark> = bookmarks
         .iter()
-        .map(|bookmark| bookmark.to_item())
+        .filter(|bookmark| bookmark.path.len() >= depth)
+        .filter(|bookmark| {
+            category
+                .is_none_or(|category| bookmark.path[..depth].join("/").to_lowercase() == category)
+        })
         .collect();
-    return if matched_bookmarks.is_empty() {
-        vec![default(query, default_search_url)]
+    let folders = in_category
+        .iter()
+        .filter(|bookmark| bookmark.path.len() > depth)
+        .map(|bookmark| bookmark.path[..=depth].join("/"))
+        .unique()
+        .map(|folder| {
+            folder_item(&folder, icons)
+                .subtitle("Browse category →")
+                .autocomplete(query::scope(&folder))
+                .valid(false)
+        });
+    let bookmarks = in_category
+        .iter()
+        .filter(|bookmark| bookmark.path.len() == depth)
+        .map(|bookmark| bookmark.to_item(actions));
+    folders.chain(bookmarks).collect()
+}
+
+/// Keeps the bookmarks matching the query, ordered by their matching score
+/// improved by how frequently and recently they were opened. Bookmarks with
+/// the query as keyword come first.
+fn sort_and_filter_matching_bookmarks(
+    bookmarks: Vec<Bookmark>,
+    query: String,
+    fields: &[Field],
+    history: &History,
+) -> Vec<Bookmark> {
+    let typed = query;
+    let query = Query::parse(&typed);
+    let now = history::now();
+    bookmarks
+        .iter()
+        .filter(|bookmark| query.matches_category(bookmark) && query.matches_tags(bookmark))
+        .map(|bookmark| bookmark.match_query(&query.text, fields))
+        .filter(|(_, is_keyword, score)| query.text.is_empty() || *is_keyword || *score < 0)
+        .sorted_by_key(|(bookmark, is_keyword, score)| {
+            let ranking = score
+                - history.boost(&bookmark.link, now)
+                - history.query_boost(&typed, &bookmark.link);
+            (!is_keyword, ranking)
+        })
+        .map(|(bookmark, _, _)| bookmark)
+        .collect()
+}
+
+fn to_items(
+    bookmarks: Vec<Bookmark>,
+    query: String,
+    fields: &[Field],
+    history: &History,
+    search_engines: &[SearchEngine],
+    actions: &Actions,
+    icons: &Icons,
+) -> Vec<Item> {
+    if let Some(partial) = query::partial_category(&query) {
+        let categories = category_items(&bookmarks, partial, icons);
+        if !categories.is_empty() {
+            return categories;
+        }
+    }
+    let parsed = Query::parse(&query);
+    if let (Some(category), "", true) = (
+        &parsed.category,
+        parsed.text.as_str(),
+        parsed.tags.is_empty(),
+    ) {
+        let items = browse_items(&bookmarks, Some(category), actions, icons);
+        if !items.is_empty() {
+            return items;
+        }
+    }
+    let matched_bookmarks: Vec<Item> =
+        sort_and_filter_matching_bookmarks(bookmarks, query.clone(), fields, history)
+            .iter()
+            .map(|bookmark| bookmark.to_item(actions))
+            .collect();
+    if matched_bookmarks.is_empty() {
+        // Searches the web without the category and tags, which only make
+        // sense for the bookmarks, unless nothing else was typed.
+        let text = if parsed.text.is_empty() {
+            query
+        } else {
+            parsed.text
+        };
+        search_engines
+            .iter()
+            .map(|search_engine| default(text.to_owned(), search_engine))
+            .collect()
     } else {
         matched_bookmarks
+    }
+}
+
+fn search(query: Option<&str>) -> Result<()> {
+    let items = search_items(query).unwrap_or_else(|error| vec![error.to_item()]);
+    powerpack::output(items)?;
+    Ok(())
+}
+
+/// Returns the items for a query, or the error to show instead.
+fn search_items(query: Option<&str>) -> Result<Vec<Item>, Error> {
+    let bookmarks_file = env::var("BOOKMARKS_FILE")
+        .ok()
+        .filter(|sources| !sources.trim().is_empty())
+        .ok_or(Error::MissingVariable("BOOKMARKS_FILE"))?;
+    let default_search_url =
+        env::var("DEFAULT_SEARCH_URL").map_err(|_| Error::MissingVariable("DEFAULT_SEARCH_URL"))?;
+    let search_engines = SearchEngine::parse_list(&default_search_url);
+    let actions = Actions::from_env()?;
+
+    let fields = match env::var("SEARCH_FIELDS") {
+        Ok(fields) if !fields.trim().is_empty() => {
+            Field::parse_list(&fields).map_err(|error| Error::InvalidVariable {
+                name: "SEARCH_FIELDS",
+                message: error.to_string(),
+            })?
+        }
+        _ => Field::ALL.to_vec(),
+    };
+    let history = history::path()
+        .and_then(|path| History::load(&path).ok())
+        .unwrap_or_default();
+
+    let icons = Icons::from_env();
+    let (bookmarks, skipped) = sources::read_all(&bookmarks_file)?;
+    let bookmarks: Vec<Bookmark> = bookmarks
+        .into_iter()
+        .map(|bookmark| icons.assign(bookmark))
+        .collect();
+    let arg = query.map(str::trim_start);
+    if let Some(path) = history::last_query_path() {
+        if let Some(directory) = path.parent() {
+            let _ = fs::create_dir_all(directory);
+        }
+        let _ = fs::write(path, arg.unwrap_or_default());
+    }
+
+    let mut items = match arg {
+        Some(query) if add::parse(query).is_some() => {
+            let file = add::json_file(&bookmarks_file);
+            vec![add::item(query, &bookmarks, file.as_deref())]
+        }
+        None | Some("") => {
+            let items = browse_items(&bookmarks, None, &actions, &icons);
+            if items.is_empty() {
+                let default_search_url = search_engines
+                    .first()
+                    .map(|search_engine| search_engine.url_for(""))
+                    .unwrap_or_default();
+                vec![empty(default_search_url)]
+            } else {
+                items
+            }
+        }
+        Some(query) => to_items(
+            bookmarks,
+            String::from(query),
+            &fields,
+            &history,
+            &search_engines,
+            &actions,
+            &icons,
+        ),
     };
+    items.extend(error::skipped_item(&skipped));
+    Ok(items)
 }
 
-fn main() -> Result<()> {
-    let bookmarks_file = env::var("BOOKMARKS_FILE").expect("BOOKMARKS_FILE not set");
-    let default_search_url = env::var("DEFAULT_SEARCH_URL").expect("DEFAULT_SEARCH_URL not set");
-
-    let contents =
-        fs::read_to_string(bookmarks_file).expect("Something went wrong reading the file");
-    let bookmarks = read_bookmarks(contents);
-    let arg = env::args()
-        .nth(1)
+/// Returns the link the usage of an opened link is recorded under, which is
+/// the link of the bookmark it belongs to, unfilled for links with
+/// placeholders. Other links, like those of fallback searches and error
+/// items, are not recorded.
+fn recorded_link<'a>(bookmarks: &'a [Bookmark], opened: &str) -> Option<&'a str> {
+    let belongs = |bookmark: &&Bookmark| bookmark.link == opened;
+    let filled = |bookmark: &&Bookmark| {
+        template::is_template(&bookmark.link) && template::matches(&bookmark.link, opened)
+    };
+    bookmarks
+        .iter()
+        .find(belongs)
+        .or_else(|| bookmarks.iter().find(filled))
+        .map(|bookmark| bookmark.link.as_str())
+}
+
+/// Records that a bookmark was opened, and for which query it was chosen.
+fn record(opened: &str) -> Result<()> {
+    let bookmarks_file = env::var("BOOKMARKS_FILE").context("BOOKMARKS_FILE not set")?;
+    let (bookmarks, _) = sources::read_all(&bookmarks_file)?;
+    let link = match recorded_link(&bookmarks, opened) {
+        Some(link) => link,
+        None => return Ok(()),
+    };
+    let path = history::path().context("workflow data directory unknown")?;
+    let mut history = History::load(&path)?;
+    history.record(link, history::now());
+    if let Some(query) = history::last_query_path().and_then(|path| fs::read_to_string(path).ok()) {
+        history.record_query(&query, link);
+    }
+    history.save(&path)
+}
+
+/// Opens the links of the selected item, one per line, and records that it
+/// was opened if it is a bookmark. Adds the bookmark instead if the item previewed an addition.
+fn open(links: &str) -> Result<()> {
+    if add::parse(links).is_some() {
+        return add_bookmark(links);
+    }
+    let links: Vec<&str> = links
+        .lines()
+        .map(str::trim)
+        .filter(|link| !link.is_empty())
+        .collect();
+    for link in links.iter() {
+        Command::new("open")
+            .arg(link)
+            .status()
+            .with_context(|| format!("could not open {}", link))?;
+    }
+    match links.first() {
+        Some(link) => record(link),
+        None => Ok(()),
+    }
+}
+
+/// Adds a bookmark typed like `add <url> <title> [in <category>]` to the
+/// first json file of `BOOKMARKS_FILE`.
+fn add_bookmark(query: &str) -> Result<()> {
+    let addition = match add::parse(query) {
+        Some(addition) => addition.map_err(anyhow::Error::msg)?,
+        None => bail!("usage: add [--force] <url> <title> [in <category>]"),
+    };
+    let bookmarks_file = env::var("BOOKMARKS_FILE").context("BOOKMARKS_FILE not set")?;
+    let file = add::json_file(&bookmarks_file)
+        .context("BOOKMARKS_FILE has no json file to add bookmarks to")?;
+    let (bookmarks, _) = sources::read_all(&bookmarks_file)?;
+    add::add(&file, &addition, &bookmarks)?;
+    println!("Added {} to {}", addition.link, file.display());
+    Ok(())
+}
+
+/// Adds a bookmark from the command line, refusing duplicates without
+/// `--force`.
+fn add_command(arguments: &[String]) -> Result<()> {
+    let force = arguments.iter().any(|argument| argument == "--force");
+    let words: Vec<&str> = arguments
+        .iter()
+        .map(String::as_str)
+        .filter(|argument| *argument != "--force")
+        .collect();
+    let command = if force { "add!" } else { "add" };
+    add_bookmark(&format!("{} {}", command, words.join(" ")))
+}
+
+/// Runs the action configured for a modifier key on the argument of the
+/// selected item.
+fn run(key: &str, arg: &str) -> Result<()> {
+    let actions = Actions::from_env()?;
+    match actions.get_by_name(key)? {
+        Some(Action::Open) => open(arg),
+        Some(Action::CopyUrl) | Some(Action::CopyMarkdown) => copy(arg),
+        Some(Action::AlternateBrowser) => open_in_browser(&actions, arg, &[]),
+        Some(Action::PrivateWindow) => {
+            let browser = actions.browser.as_deref().unwrap_or_default();
+            open_in_browser(&actions, arg, &[action::private_window_option(browser)])
+        }
+        Some(Action::QuickLook) | None => Ok(()),
+    }
+}
+
+fn copy(text: &str) -> Result<()> {
+    let mut pbcopy = Command::new("pbcopy")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("could not run pbcopy")?;
+    pbcopy
+        .stdin
+        .take()
+        .context("could not write to pbcopy")?
+        .write_all(text.as_bytes())?;
+    pbcopy.wait()?;
+    Ok(())
+}
+
+/// Opens the links, one per line, in the alternate browser with the given
+/// browser options.
+fn open_in_browser(actions: &Actions, links: &str, options: &[&str]) -> Result<()> {
+    let browser = actions
+        .browser
         .as_deref()
+        .context("ALTERNATE_BROWSER not set")?;
+    let links: Vec<&str> = links
+        .lines()
         .map(str::trim)
-        .map(str::to_ascii_lowercase);
+        .filter(|link| !link.is_empty())
+        .collect();
+    Command::new("open")
+        .arg("-na")
+        .arg(browser)
+        .arg("--args")
+        .args(options)
+        .args(links.iter())
+        .status()
+        .with_context(|| format!("could not open {}", browser))?;
+    match links.first() {
+        Some(link) => record(link),
+        None => Ok(()),
+    }
+}
+
+/// Exports or resets the usage history.
+fn manage_history(action: &str) -> Result<()> {
+    let path = history::path().context("workflow data directory unknown")?;
+    match action {
+        "export" => println!("{}", History::load(&path)?.to_json().pretty(2)),
+        "reset" => History::default().save(&path)?,
+        _ => bail!("unknown history action {}, use export or reset", action),
+    }
+    Ok(())
+}
+
+fn manage_favicons(action: &str) -> Result<()> {
+    if action != "refresh" {
+        bail!("unknown favicons action {}, use refresh", action);
+    }
+    let bookmarks_file = env::var("BOOKMARKS_FILE").context("BOOKMARKS_FILE not set")?;
+    let directory = favicon::directory().context("workflow cache directory unknown")?;
+    let (bookmarks, _) = sources::read_all(&bookmarks_file)?;
+    let summary = favicon::refresh(&bookmarks, &directory)?;
+    println!(
+        "fetched {} favicons into {}, {} failed",
+        summary.fetched,
+        directory.display(),
+        summary.failed
+    );
+    Ok(())
+}
 
-    let items: Vec<Item> = match arg.as_deref() {
-        None | Some("") => vec![empty(default_search_url)],
-        Some(query) => to_items(bookmarks, String::from(query), default_search_url),
+/// Checks bookmark files, by default those of `BOOKMARKS_FILE`, and prints
+/// the problems found, as json with `--json`. Exits with 1 if there are any.
+fn validate(arguments: &[String]) -> Result<()> {
+    let as_json = arguments.iter().any(|argument| argument == "--json");
+    let mut paths: Vec<PathBuf> = arguments
+        .iter()
+        .filter(|argument| *argument != "--json")
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() {
+        let bookmarks_file = env::var("BOOKMARKS_FILE").context("BOOKMARKS_FILE not set")?;
+        paths = sources::paths(&bookmarks_file)?;
+    }
+    let files: Vec<(PathBuf, Vec<lint::Issue>)> = paths
+        .into_iter()
+        .map(|path| {
+            let issues = lint::lint(&path);
+            (path, issues)
+        })
+        .collect();
+    if as_json {
+        println!("{}", lint::to_json(&files).pretty(2));
+    } else {
+        println!("{}", lint::report(&files));
+    }
+    if files.iter().any(|(_, issues)| !issues.is_empty()) {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Requests the links of bookmark files, by default those of
+/// `BOOKMARKS_FILE`, and reports the ones that are broken or redirected.
+/// Exits with 1 if any link is broken.
+fn check_links(arguments: &[String]) -> Result<()> {
+    let mut arguments = link_check::Arguments::parse(arguments)?;
+    if arguments.paths.is_empty() {
+        let bookmarks_file = env::var("BOOKMARKS_FILE").context("BOOKMARKS_FILE not set")?;
+        arguments.paths = sources::paths(&bookmarks_file)?;
+    }
+    if arguments.rewrite.is_some() && arguments.paths.len() != 1 {
+        bail!("--rewrite needs exactly one bookmark file");
+    }
+    // Checked before requesting the links, which can take a while.
+    let original = match arguments.rewrite {
+        Some(_) => {
+            let path = &arguments.paths[0];
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            if lint::json_format(&contents).is_none() {
+                bail!("--rewrite only works with bookmark files in the json format");
+            }
+            Some(contents)
+        }
+        None => None,
     };
-    powerpack::output(items)?;
+    let checked = link_check::check_files(&arguments.paths, &arguments.options)?;
+
+    let report = if arguments.as_json {
+        link_check::to_json(&checked).pretty(2)
+    } else {
+        link_check::report(&checked)
+    };
+    match arguments.report {
+        Some(path) => fs::write(&path, report + "\n")
+            .with_context(|| format!("could not write {}", path.display()))?,
+        None => println!("{}", report),
+    }
+    if let (Some(path), Some(contents)) = (arguments.rewrite, original) {
+        let (rewritten, count) = link_check::rewrite(&contents, &checked)?;
+        fs::write(&path, rewritten)
+            .with_context(|| format!("could not write {}", path.display()))?;
+        eprintln!("replaced {} redirected links in {}", count, path.display());
+    }
+    if checked.iter().any(|checked| checked.status.is_broken()) {
+        process::exit(1);
+    }
     Ok(())
 }
 
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, query @ ..] if command == "search" => search(query.first().map(String::as_str)),
+        [command, links] if command == "open" => open(links),
+        [command, key, arg] if command == "run" => run(key, arg),
+        [command, action] if command == "history" => manage_history(action),
+        [command, action] if command == "favicons" => manage_favicons(action),
+        [command, arguments @ ..] if command == "validate" => validate(arguments),
+        [command, arguments @ ..] if command == "check-links" => check_links(arguments),
+        [command, arguments @ ..] if command == "add" => add_command(arguments),
+        query => search(query.first().map(String::as_str)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use powerpack::Item;
+    use powerpack::{Icon, Item, Key, Modifier};
+
+    use crate::{
+        browse_items, category_items, read_bookmarks, recorded_link,
+        sort_and_filter_matching_bookmarks, to_items, Action, Actions, Bookmark, Field, History,
+        Icons, Problem, SearchEngine,
+    };
+
+    #[test]
+    fn keeps_the_category() {
+        let json = r#"{
+            "work": [{"href": "https://jira.test.blub", "title": "Jira"}],
+            "private": [{"href": "http://www.test.blub", "title": "Dashboard"}]
+        }"#;
+        let expected_bookmarks = vec![
+            Bookmark::new("Jira", "https://jira.test.blub").with_path(vec!["work".to_owned()]),
+            Bookmark::new("Dashboard", "http://www.test.blub")
+                .with_path(vec!["private".to_owned()]),
+        ];
+
+        let bookmarks = read_bookmarks(json.to_owned()).unwrap().0;
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+
+    #[test]
+    fn reads_optional_tags() {
+        let json = r#"{
+            "work": [{"href": "https://jira.test.blub", "title": "Jira", "tags": ["ops", "tickets"]}]
+        }"#;
+        let expected_bookmarks = vec![Bookmark::new("Jira", "https://jira.test.blub")
+            .with_path(vec!["work".to_owned()])
+            .with_tags(vec!["ops".to_owned(), "tickets".to_owned()])];
+
+        let bookmarks = read_bookmarks(json.to_owned()).unwrap().0;
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+
+    #[test]
+    fn reads_optional_keywords() {
+        let json = r#"{
+            "dev": [{"href": "https://github.test.blub/pulls", "title": "Pull requests", "keyword": "pr", "aliases": ["pulls"]}]
+        }"#;
+        let expected_bookmarks =
+            vec![
+                Bookmark::new("Pull requests", "https://github.test.blub/pulls")
+                    .with_path(vec!["dev".to_owned()])
+                    .with_keywords(vec!["pr".to_owned(), "pulls".to_owned()]),
+            ];
+
+        let bookmarks = read_bookmarks(json.to_owned()).unwrap().0;
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
 
-    use crate::{sort_and_filter_matching_bookmarks, Bookmark};
+    #[test]
+    fn reads_groups() {
+        let json = r#"{
+            "morning": [{"hrefs": ["http://a.test.blub", "http://b.test.blub"], "title": "Dashboards"}]
+        }"#;
+        let expected_bookmarks = vec![Bookmark::new("Dashboards", "http://a.test.blub")
+            .with_path(vec!["morning".to_owned()])
+            .with_links(vec!["http://b.test.blub".to_owned()])];
+
+        let bookmarks = read_bookmarks(json.to_owned()).unwrap().0;
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+
+    #[test]
+    fn skips_invalid_entries() {
+        let json = r#"{
+            "work": [{"title": 42, "href": "x"}, {"href": "https://jira.test.blub", "title": "Jira"}],
+            "private": {"href": "http://www.test.blub"}
+        }"#;
+        let expected_bookmarks = vec![
+            Bookmark::new("Jira", "https://jira.test.blub").with_path(vec!["work".to_owned()])
+        ];
+        let expected_skipped = vec![
+            Problem::InvalidEntry {
+                category: "work".to_owned(),
+                index: Some(0),
+                message: "title is not a string".to_owned(),
+            },
+            Problem::InvalidEntry {
+                category: "private".to_owned(),
+                index: None,
+                message: "is not a list of bookmarks".to_owned(),
+            },
+        ];
+
+        let (bookmarks, skipped) = read_bookmarks(json.to_owned()).unwrap();
+
+        assert_eq!(bookmarks, expected_bookmarks);
+        assert_eq!(skipped, expected_skipped);
+    }
+
+    #[test]
+    fn reports_entries_without_link() {
+        let json = r#"{"work": [{"title": "Jira", "hrefs": []}]}"#;
+
+        let (_, skipped) = read_bookmarks(json.to_owned()).unwrap();
+
+        assert_eq!(
+            skipped[0].to_string(),
+            "entry 1 of \"work\": missing href".to_owned()
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_json() {
+        let problem = read_bookmarks(String::from("[]")).unwrap_err();
+
+        assert_eq!(problem.to_string(), "expected an object of categories");
+    }
 
     #[test]
     fn does_not_matches_the_query() {
-        let bookmark = Bookmark {
-            name: String::from("Dashboard"),
-            link: String::from("http://www.test.blub"),
-        };
+        let bookmark = Bookmark::new("Dashboard", "http://www.test.blub");
 
-        let score = bookmark.calculate_matching_score("z".to_string());
+        let score = bookmark.calculate_matching_score("z".to_string(), &Field::ALL);
 
         assert_eq!(score, 0);
     }
 
     #[test]
     fn matches_the_query() {
-        let bookmark = Bookmark {
-            name: String::from("Dashboard"),
-            link: String::from("http://www.test.blub"),
-        };
+        let bookmark = Bookmark::new("Dashboard", "http://www.test.blub");
 
-        let score = bookmark.calculate_matching_score("d".to_string());
+        let score = bookmark.calculate_matching_score("d".to_string(), &Field::ALL);
 
         assert_eq!(score, -29);
     }
 
     #[test]
     fn transforms_to_item() {
-        let bookmark = Bookmark {
-            name: String::from("Dashboard"),
-            link: String::from("http://www.test.blub"),
-        };
+        let bookmark = Bookmark::new("Dashboard", "http://www.test.blub");
         let expected_item = Item::new("Dashboard")
             .subtitle("Open in browser →")
-            .arg("http://www.test.blub");
+            .uid(bookmark.uid())
+            .arg("http://www.test.blub")
+            .quicklook_url("http://www.test.blub")
+            .copy_text("http://www.test.blub")
+            .large_type_text("Dashboard\nhttp://www.test.blub");
 
-        let item = bookmark.to_item();
+        let item = bookmark.to_item(&Actions::none());
 
         assert_eq!(item, expected_item);
     }
 
+    #[test]
+    fn matches_the_category() {
+        let jira =
+            Bookmark::new("Jira", "https://jira.test.blub").with_path(vec!["work".to_owned()]);
+        let dashboard = Bookmark::new("Dashboard", "http://www.test.blub")
+            .with_path(vec!["private".to_owned()]);
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![dashboard, jira.clone()],
+            "work jira".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
+
+        assert_eq!(matching_bookmarks, vec![jira]);
+    }
+
+    #[test]
+    fn transforms_to_item_with_modifiers() {
+        let bookmark = Bookmark::new("Dashboard", "http://www.test.blub");
+        let actions = Actions::default().with_browser("Firefox");
+        let expected_item = Item::new("Dashboard")
+            .subtitle("Open in browser →")
+            .uid(bookmark.uid())
+            .arg("http://www.test.blub")
+            .quicklook_url("http://www.test.blub")
+            .copy_text("http://www.test.blub")
+            .large_type_text("Dashboard\nhttp://www.test.blub")
+            .modifier(
+                Modifier::new(Key::Command)
+                    .subtitle("Copy link")
+                    .arg("http://www.test.blub"),
+            )
+            .modifier(
+                Modifier::new(Key::Option)
+                    .subtitle("Copy [Dashboard](http://www.test.blub)")
+                    .arg("[Dashboard](http://www.test.blub)"),
+            )
+            .modifier(
+                Modifier::new(Key::Control)
+                    .subtitle("Open in Firefox")
+                    .arg("http://www.test.blub"),
+            )
+            .modifier(
+                Modifier::new(Key::Shift)
+                    .subtitle("Tap ⇧ for Quick Look")
+                    .valid(false),
+            );
+
+        let item = bookmark.to_item(&actions);
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn leaves_out_browser_modifiers_without_browser() {
+        let bookmark = Bookmark::new("Dashboard", "http://www.test.blub");
+        let actions = Actions::none().with_browser("Firefox");
+        let expected_item = Item::new("Dashboard")
+            .subtitle("Open in browser →")
+            .uid(bookmark.uid())
+            .arg("http://www.test.blub")
+            .quicklook_url("http://www.test.blub")
+            .copy_text("http://www.test.blub")
+            .large_type_text("Dashboard\nhttp://www.test.blub");
+
+        let item = bookmark.to_item(&actions);
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn disables_modifiers_without_an_action() {
+        let bookmark = Bookmark::new("Dashboard", "http://www.test.blub");
+        let actions = Actions::none()
+            .with_action(Key::Command, None)
+            .with_action(Key::Control, Some(Action::AlternateBrowser));
+        let expected_item = Item::new("Dashboard")
+            .subtitle("Open in browser →")
+            .uid(bookmark.uid())
+            .arg("http://www.test.blub")
+            .quicklook_url("http://www.test.blub")
+            .copy_text("http://www.test.blub")
+            .large_type_text("Dashboard\nhttp://www.test.blub")
+            .modifier(
+                Modifier::new(Key::Command)
+                    .subtitle("Nothing to do for ⌘ · Set MOD_CMD to choose an action")
+                    .valid(false),
+            )
+            .modifier(
+                Modifier::new(Key::Control)
+                    .subtitle("Set ALTERNATE_BROWSER to open in another browser")
+                    .valid(false),
+            );
+
+        let item = bookmark.to_item(&actions);
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn transforms_to_item_with_category() {
+        let bookmark = Bookmark::new("Jira", "https://jira.test.blub")
+            .with_path(vec!["work".to_owned(), "tickets".to_owned()]);
+        let expected_item = Item::new("Jira")
+            .subtitle("work/tickets · Open in browser →")
+            .uid(bookmark.uid())
+            .arg("https://jira.test.blub")
+            .quicklook_url("https://jira.test.blub")
+            .copy_text("https://jira.test.blub")
+            .large_type_text("Jira\nhttps://jira.test.blub");
+
+        let item = bookmark.to_item(&Actions::none());
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn transforms_to_item_with_tags() {
+        let bookmark = Bookmark::new("Jira", "https://jira.test.blub")
+            .with_path(vec!["work".to_owned()])
+            .with_tags(vec!["ops".to_owned(), "tickets".to_owned()]);
+        let expected_item = Item::new("Jira")
+            .subtitle("work · #ops #tickets · Open in browser →")
+            .uid(bookmark.uid())
+            .arg("https://jira.test.blub")
+            .quicklook_url("https://jira.test.blub")
+            .copy_text("https://jira.test.blub")
+            .large_type_text("Jira\nhttps://jira.test.blub");
+
+        let item = bookmark.to_item(&Actions::none());
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn transforms_group_to_item() {
+        let bookmark = Bookmark::new("Dashboards", "http://a.test.blub").with_links(vec![
+            "http://b.test.blub".to_owned(),
+            "http://c.test.blub".to_owned(),
+        ]);
+        let expected_item = Item::new("Dashboards")
+            .subtitle("Open 3 links →")
+            .uid(bookmark.uid())
+            .arg("http://a.test.blub\nhttp://b.test.blub\nhttp://c.test.blub")
+            .quicklook_url("http://a.test.blub")
+            .copy_text("http://a.test.blub\nhttp://b.test.blub\nhttp://c.test.blub")
+            .large_type_text(
+                "Dashboards\nhttp://a.test.blub\nhttp://b.test.blub\nhttp://c.test.blub",
+            );
+
+        let item = bookmark.to_item(&Actions::none());
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn filters_by_tag() {
+        let jira =
+            Bookmark::new("Jira", "https://jira.test.blub").with_tags(vec!["ops".to_owned()]);
+        let dashboard = Bookmark::new("Dashboard", "http://www.test.blub");
+        let bookmarks = vec![jira.clone(), dashboard];
+
+        assert_eq!(
+            sort_and_filter_matching_bookmarks(
+                bookmarks.clone(),
+                "#ops".to_owned(),
+                &Field::ALL,
+                &History::default()
+            ),
+            vec![jira.clone()]
+        );
+        assert_eq!(
+            sort_and_filter_matching_bookmarks(
+                bookmarks,
+                "#ops dash".to_owned(),
+                &Field::ALL,
+                &History::default()
+            ),
+            vec![]
+        );
+    }
+
     #[test]
     fn sorts_and_keep_matchting_bookmarks() {
-        let bookmark1 = Bookmark {
-            name: String::from("Dashboard"),
-            link: String::from("http://www.test.blub"),
-        };
-        let bookmark2 = Bookmark {
-            name: String::from("Bookmarks"),
-            link: String::from("http://www.bookmarks.blub"),
-        };
+        let bookmark1 = Bookmark::new("Dashboard", "http://www.test.blub");
+        let bookmark2 = Bookmark::new("Bookmarks", "http://www.bookmarks.blub");
         let bookmarks = vec![bookmark1.clone(), bookmark2.clone()];
         let expected_bookmarks = vec![bookmark1.clone(), bookmark2.clone()];
 
-        let matching_bookmarks = sort_and_filter_matching_bookmarks(bookmarks, "o".to_owned());
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            bookmarks,
+            "o".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
 
         assert_eq!(matching_bookmarks, expected_bookmarks);
     }
 
     #[test]
     fn removes_not_matchting_bookmarks() {
-        let bookmark1 = Bookmark {
-            name: String::from("Dashboard"),
-            link: String::from("http://www.test.blub"),
-        };
-        let bookmark2 = Bookmark {
-            name: String::from("Bookmarks"),
-            link: String::from("http://www.bookmarks.blub"),
-        };
+        let bookmark1 = Bookmark::new("Dashboard", "http://www.test.blub");
+        let bookmark2 = Bookmark::new("Bookmarks", "http://www.bookmarks.blub");
         let bookmarks = vec![bookmark1.clone(), bookmark2.clone()];
         let expected_bookmarks = vec![bookmark1.clone()];
 
-        let matching_bookmarks = sort_and_filter_matching_bookmarks(bookmarks, "d".to_owned());
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            bookmarks,
+            "d".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
 
         assert_eq!(matching_bookmarks, expected_bookmarks);
     }
+
+    #[test]
+    fn restricts_to_the_category() {
+        let jira =
+            Bookmark::new("Jira", "https://jira.test.blub").with_path(vec!["work".to_owned()]);
+        let dashboard = Bookmark::new("Dashboard", "http://www.test.blub")
+            .with_path(vec!["private".to_owned()]);
+        let bookmarks = vec![jira.clone(), dashboard.clone()];
+
+        assert_eq!(
+            sort_and_filter_matching_bookmarks(
+                bookmarks.clone(),
+                "@work a".to_owned(),
+                &Field::ALL,
+                &History::default()
+            ),
+            vec![jira.clone()]
+        );
+        assert_eq!(
+            sort_and_filter_matching_bookmarks(
+                bookmarks,
+                "private:".to_owned(),
+                &Field::ALL,
+                &History::default()
+            ),
+            vec![dashboard]
+        );
+    }
+
+    #[test]
+    fn completes_categories() {
+        let bookmarks = vec![
+            Bookmark::new("Jira", "https://jira.test.blub")
+                .with_path(vec!["work".to_owned(), "tickets".to_owned()]),
+            Bookmark::new("Dashboard", "http://www.test.blub")
+                .with_path(vec!["private".to_owned()]),
+        ];
+        let expected_items = vec![
+            Item::new("work")
+                .subtitle("Search in category →")
+                .autocomplete("@work ")
+                .valid(false),
+            Item::new("work/tickets")
+                .subtitle("Search in category →")
+                .autocomplete("@work/tickets ")
+                .valid(false),
+        ];
+
+        let items = category_items(&bookmarks, "wo", &Icons::default());
+
+        assert_eq!(items, expected_items);
+        assert_eq!(
+            category_items(&bookmarks, "Wo", &Icons::default()),
+            expected_items
+        );
+    }
+
+    #[test]
+    fn browses_the_top_level() {
+        let bookmarks = vec![
+            Bookmark::new("Jira", "https://jira.test.blub")
+                .with_path(vec!["work".to_owned(), "tickets".to_owned()]),
+            Bookmark::new("Dashboard", "http://www.test.blub"),
+        ];
+        let expected_items = vec![
+            Item::new("work")
+                .subtitle("Browse category →")
+                .autocomplete("@work ")
+                .valid(false),
+            Item::new("Dashboard")
+                .subtitle("Open in browser →")
+                .uid(bookmarks[1].uid())
+                .arg("http://www.test.blub")
+                .quicklook_url("http://www.test.blub")
+                .copy_text("http://www.test.blub")
+                .large_type_text("Dashboard\nhttp://www.test.blub"),
+        ];
+
+        let items = browse_items(&bookmarks, None, &Actions::none(), &Icons::default());
+
+        assert_eq!(items, expected_items);
+    }
+
+    #[test]
+    fn browses_into_a_category() {
+        let bookmarks = vec![
+            Bookmark::new("Jira", "https://jira.test.blub")
+                .with_path(vec!["Work".to_owned(), "tickets".to_owned()]),
+            Bookmark::new("Wiki", "https://wiki.test.blub").with_path(vec!["Work".to_owned()]),
+            Bookmark::new("Dashboard", "http://www.test.blub"),
+        ];
+        let expected_items = vec![
+            Item::new("Work/tickets")
+                .subtitle("Browse category →")
+                .autocomplete("@Work/tickets ")
+                .valid(false),
+            Item::new("Wiki")
+                .subtitle("Work · Open in browser →")
+                .uid(bookmarks[1].uid())
+                .arg("https://wiki.test.blub")
+                .quicklook_url("https://wiki.test.blub")
+                .copy_text("https://wiki.test.blub")
+                .large_type_text("Wiki\nhttps://wiki.test.blub"),
+        ];
+
+        let items = browse_items(
+            &bookmarks,
+            Some("work"),
+            &Actions::none(),
+            &Icons::default(),
+        );
+
+        assert_eq!(items, expected_items);
+    }
+
+    #[test]
+    fn derives_a_stable_uid_from_link_and_source() {
+        let bookmark = Bookmark::new("Jira", "https://jira.test.blub/browse/{query}");
+
+        assert_eq!(bookmark.uid(), "e1cd164f0490331b");
+        assert_eq!(
+            bookmark
+                .clone()
+                .with_arguments(vec!["ABC-1".to_owned()])
+                .uid(),
+            bookmark.uid()
+        );
+        assert_ne!(
+            bookmark.clone().with_source("team.json").uid(),
+            bookmark.uid()
+        );
+    }
+
+    #[test]
+    fn transforms_to_item_with_icon() {
+        let json = r#"{"work": [{"href": "https://jira.test.blub", "title": "Jira", "icon": "icons/jira.png"}]}"#;
+        let bookmark = read_bookmarks(json.to_owned()).unwrap().0.remove(0);
+        let expected_item = Item::new("Jira")
+            .subtitle("work · Open in browser →")
+            .uid(bookmark.uid())
+            .arg("https://jira.test.blub")
+            .quicklook_url("https://jira.test.blub")
+            .copy_text("https://jira.test.blub")
+            .large_type_text("Jira\nhttps://jira.test.blub")
+            .icon(Icon::with_image("icons/jira.png"));
+
+        let item = bookmark.to_item(&Actions::none());
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn shows_category_icons_on_folders() {
+        let bookmarks = vec![
+            Bookmark::new("Jira", "https://jira.test.blub").with_path(vec!["work".to_owned()]),
+            Bookmark::new("Wiki", "https://wiki.test.blub").with_path(vec!["private".to_owned()]),
+        ];
+        let icons = Icons::parse_list("work=icons/work.png");
+        let expected_items = vec![
+            Item::new("work")
+                .icon(Icon::with_image("icons/work.png"))
+                .subtitle("Browse category →")
+                .autocomplete("@work ")
+                .valid(false),
+            Item::new("private")
+                .subtitle("Browse category →")
+                .autocomplete("@private ")
+                .valid(false),
+        ];
+
+        let items = browse_items(&bookmarks, None, &Actions::none(), &icons);
+
+        assert_eq!(items, expected_items);
+    }
+
+    #[test]
+    fn matches_the_link() {
+        let grafana = Bookmark::new("Production", "https://grafana.test.blub/d/prod");
+        let dashboard = Bookmark::new("Dashboard", "http://www.test.blub");
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![dashboard, grafana.clone()],
+            "grafana".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
+
+        assert_eq!(matching_bookmarks, vec![grafana]);
+    }
+
+    #[test]
+    fn ranks_title_above_link_matches() {
+        let title_match = Bookmark::new("Grafana", "http://www.test.blub");
+        let link_match = Bookmark::new("Production", "https://grafana.test.blub");
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![link_match.clone(), title_match.clone()],
+            "grafana".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
+
+        assert_eq!(matching_bookmarks, vec![title_match, link_match]);
+    }
+
+    #[test]
+    fn only_matches_configured_fields() {
+        let bookmark = Bookmark::new("Production", "https://grafana.test.blub");
+
+        let score = bookmark.calculate_matching_score("grafana".to_string(), &[Field::Title]);
+
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    fn matches_words_in_any_order_and_field() {
+        let bookmark = Bookmark::new("Production dashboard", "https://grafana.test.blub")
+            .with_path(vec!["work".to_owned()])
+            .with_tags(vec!["monitoring".to_owned()]);
+
+        for query in ["dashboard prod", "grafana work", "monitoring prod"] {
+            let score = bookmark.calculate_matching_score(query.to_string(), &Field::ALL);
+
+            assert!(score < 0, "{} did not match", query);
+        }
+    }
+
+    #[test]
+    fn requires_every_word_to_match() {
+        let bookmark = Bookmark::new("Production dashboard", "https://grafana.test.blub");
+
+        let score = bookmark.calculate_matching_score("prod zzz".to_string(), &Field::ALL);
+
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    fn sums_up_the_scores_of_words() {
+        let bookmark = Bookmark::new("Production dashboard", "https://grafana.test.blub");
+
+        let prod = bookmark.calculate_matching_score("prod".to_string(), &Field::ALL);
+        let both = bookmark.calculate_matching_score("prod dash".to_string(), &Field::ALL);
+
+        assert!(both < prod);
+    }
+
+    #[test]
+    fn ranks_frequently_opened_bookmarks_first() {
+        let dashboard = Bookmark::new("Dashboard", "http://www.test.blub");
+        let dashboards = Bookmark::new("Dashboards", "http://www.dashboards.blub");
+        let mut history = History::default();
+        history.record("http://www.dashboards.blub", crate::history::now());
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![dashboard.clone(), dashboards.clone()],
+            "dashboard".to_owned(),
+            &Field::ALL,
+            &history,
+        );
+
+        assert_eq!(matching_bookmarks, vec![dashboards, dashboard]);
+    }
+
+    #[test]
+    fn only_records_links_of_bookmarks() {
+        let bookmarks = vec![Bookmark::new("Dashboard", "http://www.test.blub")];
+
+        assert_eq!(
+            recorded_link(&bookmarks, "http://www.test.blub"),
+            Some("http://www.test.blub")
+        );
+        assert_eq!(
+            recorded_link(&bookmarks, "https://search.test.blub/?q=dash"),
+            None
+        );
+        assert_eq!(recorded_link(&bookmarks, "/Users/me/bookmarks.json"), None);
+    }
+
+    #[test]
+    fn ranks_templates_opened_with_other_arguments_first() {
+        let jira = Bookmark::new("Jira", "https://jira.test.blub/browse/{query}");
+        let jenkins = Bookmark::new("Jenkins", "https://jenkins.test.blub/job/{query}");
+        let bookmarks = vec![jenkins.clone(), jira.clone()];
+        let mut history = History::default();
+        let link = recorded_link(&bookmarks, "https://jira.test.blub/browse/ABC-123").unwrap();
+        history.record(link, crate::history::now());
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            bookmarks.clone(),
+            "j ABC-124".to_owned(),
+            &Field::ALL,
+            &history,
+        );
+
+        assert_eq!(link, "https://jira.test.blub/browse/{query}");
+        assert_eq!(
+            matching_bookmarks,
+            vec![
+                jira.with_arguments(vec!["ABC-124".to_owned()]),
+                jenkins.with_arguments(vec!["ABC-124".to_owned()])
+            ]
+        );
+    }
+
+    #[test]
+    fn ranks_bookmarks_chosen_for_the_query_first() {
+        let prod = Bookmark::new("Grafana prod", "https://grafana.test.blub/d/prod");
+        let stage = Bookmark::new("Grafana stage", "https://grafana.test.blub/d/stage");
+        let mut history = History::default();
+        history.record_query("gr", "https://grafana.test.blub/d/stage");
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![prod.clone(), stage.clone()],
+            "gra".to_owned(),
+            &Field::ALL,
+            &history,
+        );
+
+        assert_eq!(matching_bookmarks, vec![stage, prod]);
+    }
+
+    #[test]
+    fn ranks_exact_keywords_first() {
+        let pr = Bookmark::new("GitHub pull requests", "https://github.test.blub/pulls")
+            .with_keywords(vec!["PR".to_owned()]);
+        let prod = Bookmark::new("pr", "https://grafana.test.blub/d/prod");
+        let mut history = History::default();
+        history.record("https://grafana.test.blub/d/prod", crate::history::now());
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![prod.clone(), pr.clone()],
+            "pr".to_owned(),
+            &Field::ALL,
+            &history,
+        );
+
+        assert_eq!(matching_bookmarks, vec![pr, prod]);
+    }
+
+    #[test]
+    fn keeps_keyword_matches_that_are_no_fuzzy_match() {
+        let pr = Bookmark::new("GitHub", "https://github.test.blub/pulls")
+            .with_keywords(vec!["xyz".to_owned()]);
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![pr.clone()],
+            "xyz".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
+
+        assert_eq!(matching_bookmarks, vec![pr]);
+    }
+
+    #[test]
+    fn fills_arguments_into_templates() {
+        let jira = Bookmark::new("Jira ticket", "https://jira.test.blub/browse/{query}")
+            .with_keywords(vec!["jira".to_owned()]);
+        let expected_bookmarks = vec![jira.clone().with_arguments(vec!["ABC-123".to_owned()])];
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![jira],
+            "jira ABC-123".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
+
+        assert_eq!(matching_bookmarks, expected_bookmarks);
+        assert_eq!(
+            matching_bookmarks[0].url(),
+            "https://jira.test.blub/browse/ABC-123"
+        );
+    }
+
+    #[test]
+    fn takes_the_words_after_the_first_as_arguments() {
+        let grafana = Bookmark::new("Grafana host", "https://grafana.test.blub/d/x?var-host={1}");
+
+        let matching_bookmarks = sort_and_filter_matching_bookmarks(
+            vec![grafana.clone()],
+            "grafana host".to_owned(),
+            &Field::ALL,
+            &History::default(),
+        );
+
+        assert_eq!(
+            matching_bookmarks,
+            vec![grafana.with_arguments(vec!["host".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn transforms_template_to_item_with_preview() {
+        let bookmark = Bookmark::new(
+            "Grafana host",
+            "https://grafana.test.blub/d/x?var-host={1}&var-env={2}",
+        )
+        .with_arguments(vec!["web 1".to_owned(), "prod".to_owned()]);
+        let expected_item = Item::new("Grafana host")
+            .subtitle("Open https://grafana.test.blub/d/x?var-host=web%201&var-env=prod →")
+            .uid(bookmark.uid())
+            .arg("https://grafana.test.blub/d/x?var-host=web%201&var-env=prod")
+            .quicklook_url("https://grafana.test.blub/d/x?var-host=web%201&var-env=prod")
+            .copy_text("https://grafana.test.blub/d/x?var-host=web%201&var-env=prod")
+            .large_type_text(
+                "Grafana host\nhttps://grafana.test.blub/d/x?var-host=web%201&var-env=prod",
+            );
+
+        let item = bookmark.to_item(&Actions::none());
+
+        assert_eq!(item, expected_item);
+    }
+
+    #[test]
+    fn searches_the_query_on_every_engine_when_nothing_matches() {
+        let search_engines = SearchEngine::parse_list(
+            "https://search.test.blub/?q={query} Wiki=https://wiki.test.blub/?text={query}",
+        );
+        let expected_items = vec![
+            Item::new("nothing found for prod db, try search on website")
+                .subtitle("Open them →")
+                .arg("https://search.test.blub/?q=prod%20db"),
+            Item::new("nothing found for prod db, try search on Wiki")
+                .subtitle("Open them →")
+                .arg("https://wiki.test.blub/?text=prod%20db"),
+        ];
+
+        let items = to_items(
+            vec![Bookmark::new("Dashboard", "http://www.test.blub")],
+            "prod db".to_owned(),
+            &Field::ALL,
+            &History::default(),
+            &search_engines,
+            &Actions::none(),
+            &Icons::default(),
+        );
+
+        assert_eq!(items, expected_items);
+    }
+
+    #[test]
+    fn searches_without_category_and_tags() {
+        let search_engines = SearchEngine::parse_list("https://search.test.blub/?q={query}");
+        let expected_items = vec![Item::new("nothing found for dash, try search on website")
+            .subtitle("Open them →")
+            .arg("https://search.test.blub/?q=dash")];
+
+        let items = to_items(
+            vec![Bookmark::new("Dashboard", "http://www.test.blub")],
+            "@work #ops dash".to_owned(),
+            &Field::ALL,
+            &History::default(),
+            &search_engines,
+            &Actions::none(),
+            &Icons::default(),
+        );
+
+        assert_eq!(items, expected_items);
+    }
 }
@@ -0,0 +1,163 @@
+use crate::Bookmark;
+
+/// A search query, optionally restricted to a category with `@work dash` or
+/// `work: dash` and to tags with `#tag`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Query {
+    /// The category, lower case, including its subfolders, e.g. `work/tickets`.
+    pub category: Option<String>,
+    /// The tags, lower case, that a bookmark must all have.
+    pub tags: Vec<String>,
+    /// The text to match against the bookmarks.
+    pub text: String,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Query {
+        let mut parsed = Query::default();
+        let mut rest = query;
+        if let Some((category, text)) = query.split_once(':') {
+            if !category.trim().is_empty() && (text.is_empty() || text.starts_with(' ')) {
+                parsed.category = Some(category.trim().to_lowercase());
+                rest = text;
+            }
+        }
+        let mut words = Vec::new();
+        for word in rest.split_whitespace() {
+            if let Some(name) = word.strip_prefix('@').filter(|name| !name.is_empty()) {
+                parsed.category = Some(name.to_lowercase());
+            } else if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+                parsed.tags.push(tag.to_lowercase());
+            } else {
+                words.push(word);
+            }
+        }
+        parsed.text = words.join(" ");
+        parsed
+    }
+
+    /// Returns true if the bookmark is in the category of the query or one of
+    /// its subfolders.
+    pub fn matches_category(&self, bookmark: &Bookmark) -> bool {
+        match &self.category {
+            None => true,
+            Some(category) => {
+                let path = bookmark.category().to_lowercase();
+                path == *category || path.starts_with(&format!("{}/", category))
+            }
+        }
+    }
+
+    /// Returns true if the bookmark has all tags of the query.
+    pub fn matches_tags(&self, bookmark: &Bookmark) -> bool {
+        self.tags.iter().all(|tag| {
+            bookmark
+                .tags
+                .iter()
+                .any(|bookmark_tag| bookmark_tag.to_lowercase() == *tag)
+        })
+    }
+}
+
+/// Returns the partially typed category if the query is only `@` followed
+/// by the start of a category name.
+pub fn partial_category(query: &str) -> Option<&str> {
+    query
+        .strip_prefix('@')
+        .filter(|name| !name.contains(char::is_whitespace))
+}
+
+/// Returns the query that searches within a category.
+pub fn scope(category: &str) -> String {
+    if category.contains(char::is_whitespace) {
+        format!("{}: ", category)
+    } else {
+        format!("@{} ", category)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{partial_category, scope, Query};
+    use crate::Bookmark;
+
+    #[test]
+    fn parses_plain_queries() {
+        let query = Query::parse("prod dash");
+
+        assert_eq!(query.category, None);
+        assert_eq!(query.text, "prod dash");
+    }
+
+    #[test]
+    fn parses_at_category() {
+        let query = Query::parse("@Work dash");
+
+        assert_eq!(query.category, Some("work".to_owned()));
+        assert_eq!(query.text, "dash");
+    }
+
+    #[test]
+    fn parses_colon_category() {
+        assert_eq!(
+            Query::parse("my work: dash"),
+            Query {
+                category: Some("my work".to_owned()),
+                text: "dash".to_owned(),
+                ..Query::default()
+            }
+        );
+        assert_eq!(Query::parse("work:").category, Some("work".to_owned()));
+        assert_eq!(Query::parse("http://www.test.blub").category, None);
+    }
+
+    #[test]
+    fn parses_tags() {
+        assert_eq!(
+            Query::parse("work: #Ops dash #jira"),
+            Query {
+                category: Some("work".to_owned()),
+                tags: vec!["ops".to_owned(), "jira".to_owned()],
+                text: "dash".to_owned(),
+            }
+        );
+        assert_eq!(Query::parse("c# #").text, "c# #");
+    }
+
+    #[test]
+    fn matches_all_tags() {
+        let bookmark = Bookmark::new("Jira", "https://jira.test.blub")
+            .with_tags(vec!["Ops".to_owned(), "tickets".to_owned()]);
+
+        assert!(Query::parse("#ops").matches_tags(&bookmark));
+        assert!(Query::parse("#ops #tickets").matches_tags(&bookmark));
+        assert!(!Query::parse("#ops #dev").matches_tags(&bookmark));
+        assert!(Query::parse("jira").matches_tags(&bookmark));
+    }
+
+    #[test]
+    fn matches_category_and_subfolders() {
+        let query = Query::parse("@work");
+        let jira = Bookmark::new("Jira", "https://jira.test.blub")
+            .with_path(vec!["Work".to_owned(), "tickets".to_owned()]);
+        let workshop = Bookmark::new("Workshop", "http://www.test.blub")
+            .with_path(vec!["workshop".to_owned()]);
+
+        assert!(query.matches_category(&jira));
+        assert!(!query.matches_category(&workshop));
+    }
+
+    #[test]
+    fn detects_partial_categories() {
+        assert_eq!(partial_category("@wo"), Some("wo"));
+        assert_eq!(partial_category("@"), Some(""));
+        assert_eq!(partial_category("@work "), None);
+        assert_eq!(partial_category("work"), None);
+    }
+
+    #[test]
+    fn scopes_queries() {
+        assert_eq!(scope("work/tickets"), "@work/tickets ");
+        assert_eq!(scope("my work"), "my work: ");
+    }
+}
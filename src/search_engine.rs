@@ -0,0 +1,77 @@
+use crate::template;
+
+/// A website to search on when no bookmark matches.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SearchEngine {
+    pub name: Option<String>,
+    /// The search url, where `{query}` is replaced by the query.
+    pub url: String,
+}
+
+impl SearchEngine {
+    /// Parses whitespace separated search urls, each optionally named like
+    /// `Google=https://www.google.com/search?q={query}`.
+    pub fn parse_list(search_urls: &str) -> Vec<SearchEngine> {
+        search_urls
+            .split_whitespace()
+            .map(|entry| match entry.split_once('=') {
+                Some((name, url)) if !name.contains([':', '/', '?']) => SearchEngine {
+                    name: Some(name.to_owned()),
+                    url: url.to_owned(),
+                },
+                _ => SearchEngine {
+                    name: None,
+                    url: entry.to_owned(),
+                },
+            })
+            .collect()
+    }
+
+    /// Returns the url searching for the query.
+    pub fn url_for(&self, query: &str) -> String {
+        template::fill(&self.url, &[query.to_owned()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search_engine::SearchEngine;
+
+    #[test]
+    fn parses_named_and_unnamed_engines() {
+        let engines = SearchEngine::parse_list(
+            "https://search.test.blub/?q={query}  Wiki=https://wiki.test.blub/search?text={query}",
+        );
+
+        assert_eq!(
+            engines,
+            vec![
+                SearchEngine {
+                    name: None,
+                    url: "https://search.test.blub/?q={query}".to_owned(),
+                },
+                SearchEngine {
+                    name: Some("Wiki".to_owned()),
+                    url: "https://wiki.test.blub/search?text={query}".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fills_in_the_query() {
+        let engine = SearchEngine::parse_list("https://search.test.blub/?q={query}").remove(0);
+
+        assert_eq!(
+            engine.url_for("prod dashboard"),
+            "https://search.test.blub/?q=prod%20dashboard"
+        );
+    }
+
+    #[test]
+    fn keeps_urls_without_placeholder() {
+        let engine = SearchEngine::parse_list("https://search.test.blub/?a=b").remove(0);
+
+        assert_eq!(engine.url_for("prod"), "https://search.test.blub/?a=b");
+    }
+}
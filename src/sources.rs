@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Problem};
+use crate::{read_bookmarks, Bookmark};
+
+mod bplist;
+pub mod chromium;
+pub mod firefox;
+pub mod netscape;
+pub mod safari;
+mod sqlite;
+
+/// Reads the bookmarks of all sources in a `:` separated list of files,
+/// directories and globs like `~/bookmarks/*.json`.
+///
+/// A link that appears in several sources is only kept from the first source
+/// it appears in, so sources listed first take precedence. Invalid entries
+/// are skipped and returned separately, like files of directories and globs
+/// that can't be read. Only files listed themselves have to be readable.
+pub fn read_all(sources: &str) -> Result<(Vec<Bookmark>, Vec<Error>), Error> {
+    let mut bookmarks = Vec::new();
+    let mut skipped = Vec::new();
+    for (path, listed) in listed_paths(sources)? {
+        let source = path.display().to_string();
+        let to_error = |problem| Error::File {
+            path: path.to_owned(),
+            problem,
+        };
+        let (read, problems) = match read(&path) {
+            Ok(read) => read,
+            Err(problem) if listed => return Err(to_error(problem)),
+            Err(problem) => {
+                skipped.push(to_error(problem));
+                continue;
+            }
+        };
+        skipped.extend(problems.into_iter().map(to_error));
+        bookmarks.push(
+            read.into_iter()
+                .map(|bookmark| bookmark.with_source(source.to_owned()))
+                .collect(),
+        );
+    }
+    Ok((merge(bookmarks), skipped))
+}
+
+/// Returns the files of all sources in a `:` separated list.
+pub fn paths(sources: &str) -> Result<Vec<PathBuf>, Error> {
+    Ok(listed_paths(sources)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// Returns the files of all sources and whether each one was listed itself
+/// rather than found in a directory or by a glob.
+fn listed_paths(sources: &str) -> Result<Vec<(PathBuf, bool)>, Error> {
+    let mut paths = Vec::new();
+    for source in env::split_paths(sources).filter(|source| !source.as_os_str().is_empty()) {
+        let expanded = expand(&source)?;
+        let listed = expanded == [source.to_owned()];
+        paths.extend(expanded.into_iter().map(|path| (path, listed)));
+    }
+    Ok(paths)
+}
+
+/// Merges the bookmarks of several sources, dropping links already seen in
+/// an earlier source. Duplicates within one source are kept.
+pub fn merge(sources: Vec<Vec<Bookmark>>) -> Vec<Bookmark> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut merged = Vec::new();
+    for bookmarks in sources {
+        let links: Vec<String> = bookmarks.iter().map(|b| b.link.to_owned()).collect();
+        merged.extend(
+            bookmarks
+                .into_iter()
+                .filter(|bookmark| !seen.contains(&bookmark.link)),
+        );
+        seen.extend(links);
+    }
+    merged
+}
+
+/// Returns the files of a source: the file itself, the files of a directory
+/// or the files matching a glob in the last path component.
+fn expand(source: &Path) -> Result<Vec<PathBuf>, Error> {
+    let pattern = source
+        .file_name()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+    let (directory, pattern) = if source.is_dir() {
+        (source, "*")
+    } else if pattern.contains(['*', '?']) {
+        (source.parent().unwrap_or_else(|| Path::new(".")), pattern)
+    } else {
+        return Ok(vec![source.to_path_buf()]);
+    };
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)
+        .map_err(|error| Error::File {
+            path: directory.to_path_buf(),
+            problem: match error.kind() {
+                ErrorKind::NotFound => Problem::NotFound,
+                _ => Problem::Unreadable(error.to_string()),
+            },
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+            !name.starts_with('.') && matches_glob(pattern, name)
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Matches a file name against a pattern with `*` and `?` wildcards.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Reads the bookmarks of a file, detecting its format from the content,
+/// and the entries that were skipped.
+pub fn read(path: &Path) -> Result<(Vec<Bookmark>, Vec<Problem>), Problem> {
+    let bytes = fs::read(path).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => Problem::NotFound,
+        _ => Problem::Unreadable(error.to_string()),
+    })?;
+    let unreadable = |error: anyhow::Error| Problem::Unreadable(format!("{:#}", error));
+    if bytes.starts_with(sqlite::MAGIC) {
+        if path.file_name() == Some(OsStr::new(chromium::WEB_DATA)) {
+            return complete(chromium::read_search_engines(path).map_err(unreadable)?);
+        }
+        return complete(firefox::read_bookmarks(path).map_err(unreadable)?);
+    }
+    if bytes.starts_with(bplist::MAGIC) {
+        return complete(safari::read_bookmarks(&bytes).map_err(unreadable)?);
+    }
+    let contents = String::from_utf8(bytes)
+        .map_err(|_| Problem::Unreadable(String::from("not a text file")))?;
+    if netscape::is_netscape(&contents) {
+        return complete(netscape::read_bookmarks(&contents));
+    }
+    let parsed = json::parse(&contents)?;
+    if chromium::is_chromium(&parsed) {
+        complete(chromium::read_bookmarks(&parsed))
+    } else {
+        read_bookmarks(contents)
+    }
+}
+
+/// Returns the bookmarks of a format without entries to skip.
+fn complete(bookmarks: Vec<Bookmark>) -> Result<(Vec<Bookmark>, Vec<Problem>), Problem> {
+    Ok((bookmarks, Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::error::{Error, Problem};
+    use crate::sources::{matches_glob, merge, read_all};
+    use crate::test_support::TempDir;
+    use crate::Bookmark;
+
+    #[test]
+    fn matches_globs() {
+        assert!(matches_glob("*", "bookmarks.json"));
+        assert!(matches_glob("*.json", "bookmarks.json"));
+        assert!(matches_glob("team-?.json", "team-a.json"));
+        assert!(matches_glob("*mark*", "bookmarks.json"));
+        assert!(!matches_glob("*.json", "bookmarks.html"));
+        assert!(!matches_glob("team-?.json", "team-ab.json"));
+    }
+
+    #[test]
+    fn keeps_duplicates_from_first_source() {
+        let team = vec![
+            Bookmark::new("Dashboard", "http://www.test.blub"),
+            Bookmark::new("Dashboard again", "http://www.test.blub"),
+        ];
+        let personal = vec![
+            Bookmark::new("My dashboard", "http://www.test.blub"),
+            Bookmark::new("Bookmarks", "http://www.bookmarks.blub"),
+        ];
+        let expected_bookmarks = vec![
+            Bookmark::new("Dashboard", "http://www.test.blub"),
+            Bookmark::new("Dashboard again", "http://www.test.blub"),
+            Bookmark::new("Bookmarks", "http://www.bookmarks.blub"),
+        ];
+
+        let bookmarks = merge(vec![team, personal]);
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+
+    #[test]
+    fn reads_files_directories_and_globs() {
+        let directory = TempDir::new("sources");
+        fs::create_dir_all(directory.join("team")).unwrap();
+        let personal = directory.join("personal.json");
+        let team = directory.join("team").join("team.json");
+        fs::write(
+            &personal,
+            r#"{"c": [{"href": "http://a.blub", "title": "a"}]}"#,
+        )
+        .unwrap();
+        fs::write(&team, r#"{"c": [{"href": "http://b.blub", "title": "b"}]}"#).unwrap();
+        let sources = format!(
+            "{}:{}:{}",
+            directory.join("*.json").display(),
+            directory.join("team").display(),
+            personal.display()
+        );
+        let expected_bookmarks = vec![
+            Bookmark::new("a", "http://a.blub")
+                .with_path(vec!["c".to_owned()])
+                .with_source(personal.display().to_string()),
+            Bookmark::new("b", "http://b.blub")
+                .with_path(vec!["c".to_owned()])
+                .with_source(team.display().to_string()),
+        ];
+
+        let (bookmarks, _) = read_all(&sources).unwrap();
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+
+    #[test]
+    fn skips_unreadable_files_of_directories() {
+        let directory = TempDir::new("unreadable");
+        let notes = directory.join("notes.txt");
+        fs::write(
+            directory.join("bookmarks.json"),
+            r#"{"c": [{"href": "http://a.blub", "title": "a"}]}"#,
+        )
+        .unwrap();
+        fs::write(&notes, "{ notes").unwrap();
+
+        let (bookmarks, skipped) = read_all(directory.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(
+            skipped,
+            vec![Error::File {
+                path: notes,
+                problem: Problem::InvalidJson {
+                    position: Some((1, 3)),
+                    message: String::from("unexpected character n"),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let directory = TempDir::new("missing");
+        let path = directory.join("bookmarks.json");
+
+        let error = read_all(path.to_str().unwrap()).unwrap_err();
+
+        assert_eq!(
+            error,
+            Error::File {
+                path,
+                problem: Problem::NotFound
+            }
+        );
+    }
+}
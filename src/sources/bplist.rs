@@ -0,0 +1,302 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use anyhow::{bail, Context, Result};
+
+/// The first bytes of every binary property list.
+pub const MAGIC: &[u8] = b"bplist00";
+
+/// Nested containers deeper than this are rejected, which also guards
+/// against reference cycles in malformed files.
+const MAX_DEPTH: usize = 64;
+
+/// How often each object may be decoded on average. Objects referenced from
+/// several containers are decoded again for each, so without a limit a small
+/// file sharing objects repeatedly would take exponential time.
+const DECODES_PER_OBJECT: usize = 4;
+
+/// A value of a binary property list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    Date(f64),
+    Data(Vec<u8>),
+    String(String),
+    Uid(u64),
+    Array(Vec<Value>),
+    Dictionary(HashMap<String, Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(values) => values,
+            _ => &[],
+        }
+    }
+
+    /// Returns the value of a dictionary entry, or `Null` if there is none.
+    pub fn get(&self, key: &str) -> &Value {
+        match self {
+            Value::Dictionary(entries) => entries.get(key).unwrap_or(&Value::Null),
+            _ => &Value::Null,
+        }
+    }
+}
+
+struct Trailer {
+    offset_size: usize,
+    ref_size: usize,
+    num_objects: usize,
+    top_object: usize,
+    offset_table: usize,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    trailer: Trailer,
+    /// How many objects were decoded so far.
+    decoded: Cell<usize>,
+}
+
+/// Parses a binary property list (`bplist00`).
+pub fn parse(bytes: &[u8]) -> Result<Value> {
+    if !bytes.starts_with(MAGIC) || bytes.len() < MAGIC.len() + 32 {
+        bail!("not a binary property list");
+    }
+    let trailer = &bytes[bytes.len() - 32..];
+    let reader = Reader {
+        bytes,
+        trailer: Trailer {
+            offset_size: trailer[6] as usize,
+            ref_size: trailer[7] as usize,
+            num_objects: be_uint(&trailer[8..16]) as usize,
+            top_object: be_uint(&trailer[16..24]) as usize,
+            offset_table: be_uint(&trailer[24..32]) as usize,
+        },
+        decoded: Cell::new(0),
+    };
+    reader.object(reader.trailer.top_object, 0)
+}
+
+/// Returns the position a number of bytes after another one taken from the
+/// file, failing instead of overflowing.
+fn after(position: usize, bytes: usize) -> Result<usize> {
+    position
+        .checked_add(bytes)
+        .context("truncated binary property list")
+}
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, start: usize, len: usize) -> Result<&'a [u8]> {
+        start
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(start..end))
+            .context("truncated binary property list")
+    }
+
+    fn offset(&self, object: usize) -> Result<usize> {
+        if object >= self.trailer.num_objects {
+            bail!("invalid object reference {}", object);
+        }
+        let size = self.trailer.offset_size;
+        let start = object
+            .checked_mul(size)
+            .and_then(|start| start.checked_add(self.trailer.offset_table))
+            .context("invalid offset table")?;
+        Ok(be_uint(self.slice(start, size)?) as usize)
+    }
+
+    /// Returns the length of a container and the position of its content.
+    fn length(&self, marker: u8, position: usize) -> Result<(usize, usize)> {
+        let info = (marker & 0x0f) as usize;
+        if info != 0x0f {
+            return Ok((info, after(position, 1)?));
+        }
+        let int_marker = self.slice(after(position, 1)?, 1)?[0];
+        if int_marker & 0xf0 != 0x10 {
+            bail!("invalid length marker {:#x}", int_marker);
+        }
+        let size = 1 << (int_marker & 0x0f);
+        let start = after(position, 2)?;
+        let length = be_uint(self.slice(start, size)?) as usize;
+        Ok((length, after(start, size)?))
+    }
+
+    fn refs(&self, start: usize, count: usize) -> Result<Vec<usize>> {
+        let size = self.trailer.ref_size;
+        if size == 0 {
+            bail!("invalid reference size");
+        }
+        let bytes = self.slice(start, count.checked_mul(size).context("invalid length")?)?;
+        Ok(bytes
+            .chunks(size)
+            .map(|chunk| be_uint(chunk) as usize)
+            .collect())
+    }
+
+    fn object(&self, object: usize, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("binary property list is nested too deeply");
+        }
+        self.decoded.set(self.decoded.get() + 1);
+        if self.decoded.get() > self.trailer.num_objects.saturating_mul(DECODES_PER_OBJECT) {
+            bail!("binary property list references its objects too often");
+        }
+        let position = self.offset(object)?;
+        let marker = self.slice(position, 1)?[0];
+        let value = match marker >> 4 {
+            0x0 => match marker {
+                0x08 => Value::Boolean(false),
+                0x09 => Value::Boolean(true),
+                _ => Value::Null,
+            },
+            0x1 => {
+                let size = 1 << (marker & 0x0f);
+                let bytes = self.slice(after(position, 1)?, size)?;
+                Value::Integer(be_uint(bytes) as i64)
+            }
+            0x2 => Value::Real(self.real(marker, position)?),
+            0x3 => Value::Date(self.real(0x23, position)?),
+            0x4 => {
+                let (length, start) = self.length(marker, position)?;
+                Value::Data(self.slice(start, length)?.to_vec())
+            }
+            0x5 => {
+                let (length, start) = self.length(marker, position)?;
+                let bytes = self.slice(start, length)?;
+                Value::String(bytes.iter().map(|byte| *byte as char).collect())
+            }
+            0x6 => {
+                let (length, start) = self.length(marker, position)?;
+                let bytes = self.slice(start, length.checked_mul(2).context("invalid length")?)?;
+                let units: Vec<u16> = bytes
+                    .chunks(2)
+                    .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+                    .collect();
+                Value::String(String::from_utf16(&units)?)
+            }
+            0x8 => {
+                let size = (marker & 0x0f) as usize + 1;
+                Value::Uid(be_uint(self.slice(after(position, 1)?, size)?))
+            }
+            0xa => {
+                let (length, start) = self.length(marker, position)?;
+                let values = self
+                    .refs(start, length)?
+                    .into_iter()
+                    .map(|object| self.object(object, depth + 1))
+                    .collect::<Result<_>>()?;
+                Value::Array(values)
+            }
+            0xd => {
+                let (length, start) = self.length(marker, position)?;
+                let keys = self.refs(start, length)?;
+                let values_start = length
+                    .checked_mul(self.trailer.ref_size)
+                    .and_then(|keys| keys.checked_add(start))
+                    .context("invalid length")?;
+                let values = self.refs(values_start, length)?;
+                let mut entries = HashMap::new();
+                for (key, value) in keys.into_iter().zip(values) {
+                    let key = match self.object(key, depth + 1)? {
+                        Value::String(key) => key,
+                        other => bail!("invalid dictionary key {:?}", other),
+                    };
+                    entries.insert(key, self.object(value, depth + 1)?);
+                }
+                Value::Dictionary(entries)
+            }
+            _ => bail!("unsupported object marker {:#x}", marker),
+        };
+        Ok(value)
+    }
+
+    fn real(&self, marker: u8, position: usize) -> Result<f64> {
+        let bytes = self.slice(after(position, 1)?, 1 << (marker & 0x0f))?;
+        Ok(match bytes.len() {
+            4 => f32::from_be_bytes(bytes.try_into()?) as f64,
+            8 => f64::from_be_bytes(bytes.try_into()?),
+            _ => bail!("unsupported real size {}", bytes.len()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::bplist::{parse, Value};
+
+    #[test]
+    fn parses_nested_values() {
+        let plist = parse(include_bytes!(
+            "../../tests/fixtures/Safari-Bookmarks.plist"
+        ))
+        .unwrap();
+
+        assert_eq!(plist.get("WebBookmarkFileVersion"), &Value::Integer(1));
+        assert_eq!(
+            plist.get("WebBookmarkType").as_str(),
+            Some("WebBookmarkTypeList")
+        );
+        assert_eq!(plist.get("Children").as_array().len(), 4);
+        assert_eq!(plist.get("missing"), &Value::Null);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse(b"{\"category\": []}").is_err());
+        assert!(parse(b"bplist00").is_err());
+    }
+
+    #[test]
+    fn rejects_objects_shared_exponentially() {
+        // Objects 0 to 39 are arrays referencing the next object twice, the
+        // last one is an empty array.
+        let mut bytes = b"bplist00".to_vec();
+        let mut offsets = Vec::new();
+        for object in 0..40u8 {
+            offsets.push(bytes.len() as u8);
+            bytes.extend_from_slice(&[0xa2, object + 1, object + 1]);
+        }
+        offsets.push(bytes.len() as u8);
+        bytes.push(0xa0);
+        let offset_table = bytes.len() as u64;
+        bytes.extend_from_slice(&offsets);
+        bytes.extend_from_slice(&[0; 6]);
+        bytes.extend_from_slice(&[1, 1]);
+        bytes.extend_from_slice(&(offsets.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&0u64.to_be_bytes());
+        bytes.extend_from_slice(&offset_table.to_be_bytes());
+
+        assert!(parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_offsets() {
+        let mut bytes = b"bplist00".to_vec();
+        bytes.extend_from_slice(&[0; 6]);
+        bytes.extend_from_slice(&[8, 1]);
+        bytes.extend_from_slice(&u64::MAX.to_be_bytes());
+        bytes.extend_from_slice(&(u64::MAX / 8).to_be_bytes());
+        bytes.extend_from_slice(&u64::MAX.to_be_bytes());
+
+        assert!(parse(&bytes).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use anyhow::Result;
+use json::JsonValue;
+
+use crate::sources::sqlite;
+use crate::Bookmark;
+
+/// The file name of the database holding the search engines.
+pub const WEB_DATA: &str = "Web Data";
+
+const SEARCH_ENGINES_QUERY: &str = "SELECT short_name, keyword, url FROM keywords";
+
+/// The roots of a Chromium `Bookmarks` file with the folder names they get.
+/// Bookmarks of the bookmark bar have no folder, "synced" holds those added
+/// on phones.
+const ROOTS: [(&str, Option<&str>); 3] = [
+    ("bookmark_bar", None),
+    ("other", Some("Other bookmarks")),
+    ("synced", Some("Mobile bookmarks")),
+];
+
+/// Returns true if the parsed json looks like a Chrome/Chromium/Brave/Edge/Vivaldi
+/// `Bookmarks` file.
+pub fn is_chromium(parsed: &JsonValue) -> bool {
+    parsed["roots"].is_object()
+}
+
+/// Reads all bookmarks of a Chromium `Bookmarks` file, keeping the folder path.
+pub fn read_bookmarks(parsed: &JsonValue) -> Vec<Bookmark> {
+    let mut bookmarks = Vec::new();
+    for (root, name) in ROOTS.iter() {
+        let path: Vec<String> = name.iter().map(|name| name.to_string()).collect();
+        collect(&parsed["roots"][*root], &path, &mut bookmarks);
+    }
+    bookmarks
+}
+
+/// Reads the search engines of a Chromium `Web Data` database as bookmarks,
+/// with their shortcut as keyword and `{searchTerms}` to fill in the query.
+pub fn read_search_engines(web_data: &Path) -> Result<Vec<Bookmark>> {
+    Ok(search_engines_from_rows(&sqlite::query(
+        web_data,
+        SEARCH_ENGINES_QUERY,
+    )?))
+}
+
+fn search_engines_from_rows(rows: &JsonValue) -> Vec<Bookmark> {
+    rows.members()
+        .filter_map(|row| {
+            let link = row["url"].as_str().filter(|url| url.starts_with("http"))?;
+            let name = row["short_name"].as_str().unwrap_or(link);
+            let keywords = row["keyword"]
+                .as_str()
+                .map(str::to_owned)
+                .into_iter()
+                .collect();
+            Some(
+                Bookmark::new(name, link)
+                    .with_path(vec!["Search engines".to_owned()])
+                    .with_keywords(keywords),
+            )
+        })
+        .collect()
+}
+
+/// Seconds from 1601-01-01, where Chromium timestamps start, to the unix
+/// epoch.
+const WINDOWS_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+fn collect(folder: &JsonValue, path: &[String], bookmarks: &mut Vec<Bookmark>) {
+    for node in folder["children"].members() {
+        match node["type"].as_str() {
+            Some("url") => {
+                if let (Some(name), Some(link)) = (node["name"].as_str(), node["url"].as_str()) {
+                    let bookmark = Bookmark::new(name, link).with_path(path.to_vec());
+                    // Microseconds since 1601-01-01, stored as a string.
+                    let added = node["date_added"]
+                        .as_str()
+                        .and_then(|added| added.parse::<u64>().ok())
+                        .and_then(|added| (added / 1_000_000).checked_sub(WINDOWS_EPOCH_OFFSET));
+                    bookmarks.push(match added {
+                        Some(added) => bookmark.with_added(added),
+                        None => bookmark,
+                    });
+                }
+            }
+            Some("folder") => {
+                let mut path = path.to_vec();
+                path.push(node["name"].as_str().unwrap_or_default().to_owned());
+                collect(node, &path, bookmarks);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::chromium::{is_chromium, read_bookmarks, search_engines_from_rows};
+    use crate::Bookmark;
+
+    const BOOKMARKS: &str = r#"{
+        "checksum": "f2a5e0b1c3d4",
+        "roots": {
+            "bookmark_bar": {
+                "children": [{
+                    "date_added": "13290000000000000",
+                    "id": "5",
+                    "name": "Dashboard",
+                    "type": "url",
+                    "url": "http://www.test.blub"
+                }, {
+                    "children": [{
+                        "children": [{
+                            "id": "8",
+                            "name": "Jira",
+                            "type": "url",
+                            "url": "https://jira.test.blub"
+                        }],
+                        "id": "7",
+                        "name": "tickets",
+                        "type": "folder"
+                    }],
+                    "id": "6",
+                    "name": "work",
+                    "type": "folder"
+                }],
+                "id": "1",
+                "name": "Bookmarks bar",
+                "type": "folder"
+            },
+            "other": {
+                "children": [{
+                    "id": "9",
+                    "name": "Bookmarks",
+                    "type": "url",
+                    "url": "http://www.bookmarks.blub"
+                }],
+                "id": "2",
+                "name": "Other bookmarks",
+                "type": "folder"
+            },
+            "synced": {
+                "children": [],
+                "id": "3",
+                "name": "Mobile bookmarks",
+                "type": "folder"
+            }
+        },
+        "version": 1
+    }"#;
+
+    #[test]
+    fn detects_chromium_bookmarks() {
+        assert!(is_chromium(&json::parse(BOOKMARKS).unwrap()));
+        assert!(!is_chromium(&json::parse(r#"{"category": []}"#).unwrap()));
+    }
+
+    #[test]
+    fn reads_nested_folders() {
+        let expected_bookmarks = vec![
+            Bookmark::new("Dashboard", "http://www.test.blub").with_added(1645526400),
+            Bookmark::new("Jira", "https://jira.test.blub")
+                .with_path(vec!["work".to_owned(), "tickets".to_owned()]),
+            Bookmark::new("Bookmarks", "http://www.bookmarks.blub")
+                .with_path(vec!["Other bookmarks".to_owned()]),
+        ];
+
+        let bookmarks = read_bookmarks(&json::parse(BOOKMARKS).unwrap());
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+
+    #[test]
+    fn reads_search_engines() {
+        let rows = r#"[
+            {"short_name": "Jira", "keyword": "jira", "url": "https://jira.test.blub/browse/{searchTerms}"},
+            {"short_name": "Google", "keyword": "google.com", "url": "{google:baseURL}search?q={searchTerms}"}
+        ]"#;
+        let expected_bookmarks =
+            vec![
+                Bookmark::new("Jira", "https://jira.test.blub/browse/{searchTerms}")
+                    .with_path(vec!["Search engines".to_owned()])
+                    .with_keywords(vec!["jira".to_owned()]),
+            ];
+
+        let bookmarks = search_engines_from_rows(&json::parse(rows).unwrap());
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+}
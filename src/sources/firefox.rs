@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use json::JsonValue;
+
+use crate::sources::sqlite;
+use crate::Bookmark;
+
+const QUERY: &str = "SELECT b.id, b.parent, b.type, b.title, b.guid, b.dateAdded, p.url, \
+     (SELECT group_concat(k.keyword, ' ') FROM moz_keywords k WHERE k.place_id = b.fk) AS keywords \
+     FROM moz_bookmarks b LEFT JOIN moz_places p ON p.id = b.fk \
+     ORDER BY b.parent, b.position";
+
+const TYPE_BOOKMARK: i64 = 1;
+
+/// The built-in folders of a profile, identified by their fixed guids. The
+/// invisible root and the toolbar have no folder name. Tags are skipped
+/// because they only reference bookmarks stored elsewhere.
+const ROOTS: [(&str, Option<&str>); 6] = [
+    ("root________", None),
+    ("toolbar_____", None),
+    ("menu________", Some("Bookmarks Menu")),
+    ("unfiled_____", Some("Other Bookmarks")),
+    ("mobile______", Some("Mobile Bookmarks")),
+    ("tags________", None),
+];
+
+/// Reads all bookmarks of a Firefox `places.sqlite`.
+pub fn read_bookmarks(places: &Path) -> Result<Vec<Bookmark>> {
+    Ok(from_rows(&sqlite::query(places, QUERY)?))
+}
+
+struct Folder<'a> {
+    parent: i64,
+    title: &'a str,
+    guid: &'a str,
+}
+
+/// Turns the rows of `moz_bookmarks` joined with `moz_places` into bookmarks.
+pub fn from_rows(rows: &JsonValue) -> Vec<Bookmark> {
+    let folders: HashMap<i64, Folder> = rows
+        .members()
+        .filter(|row| row["type"].as_i64() != Some(TYPE_BOOKMARK))
+        .filter_map(|row| {
+            let folder = Folder {
+                parent: row["parent"].as_i64()?,
+                title: row["title"].as_str().unwrap_or_default(),
+                guid: row["guid"].as_str().unwrap_or_default(),
+            };
+            Some((row["id"].as_i64()?, folder))
+        })
+        .collect();
+
+    // Tags are folders in the tags root, holding a bookmark per tagged link.
+    let mut tags: HashMap<&str, Vec<String>> = HashMap::new();
+    for row in rows.members() {
+        let tag = row["parent"]
+            .as_i64()
+            .and_then(|parent| folders.get(&parent));
+        let is_tag = tag
+            .and_then(|tag| folders.get(&tag.parent))
+            .is_some_and(|root| root.guid == "tags________");
+        if let (Some(tag), Some(link), true) = (tag, row["url"].as_str(), is_tag) {
+            tags.entry(link).or_default().push(tag.title.to_owned());
+        }
+    }
+
+    rows.members()
+        .filter(|row| row["type"].as_i64() == Some(TYPE_BOOKMARK))
+        .filter_map(|row| {
+            let link = row["url"]
+                .as_str()
+                .filter(|url| !url.starts_with("place:"))?;
+            let path = folder_path(&folders, row["parent"].as_i64()?)?;
+            let name = row["title"]
+                .as_str()
+                .filter(|title| !title.is_empty())
+                .unwrap_or(link);
+            let tags = tags.get(link).cloned().unwrap_or_default();
+            let keywords = row["keywords"]
+                .as_str()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect();
+            let bookmark = Bookmark::new(name, link)
+                .with_path(path)
+                .with_tags(tags)
+                .with_keywords(keywords);
+            // Microseconds since the unix epoch.
+            Some(match row["dateAdded"].as_u64() {
+                Some(added) => bookmark.with_added(added / 1_000_000),
+                None => bookmark,
+            })
+        })
+        .collect()
+}
+
+/// Returns the path of a folder, or `None` if it is not reachable from the
+/// bookmark roots.
+fn folder_path(folders: &HashMap<i64, Folder>, mut id: i64) -> Option<Vec<String>> {
+    let mut path = Vec::new();
+    loop {
+        let folder = folders.get(&id)?;
+        if let Some((guid, name)) = ROOTS.iter().find(|(guid, _)| *guid == folder.guid) {
+            if *guid == "tags________" {
+                return None;
+            }
+            path.extend(name.iter().map(|name| name.to_string()));
+            path.reverse();
+            return Some(path);
+        }
+        path.push(folder.title.to_owned());
+        id = folder.parent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::firefox::from_rows;
+    use crate::Bookmark;
+
+    const ROWS: &str = r#"[
+        {"id":1,"parent":0,"type":2,"title":"","guid":"root________","url":null},
+        {"id":2,"parent":1,"type":2,"title":"menu","guid":"menu________","url":null},
+        {"id":3,"parent":1,"type":2,"title":"toolbar","guid":"toolbar_____","url":null},
+        {"id":4,"parent":1,"type":2,"title":"tags","guid":"tags________","url":null},
+        {"id":5,"parent":1,"type":2,"title":"unfiled","guid":"unfiled_____","url":null},
+        {"id":6,"parent":1,"type":2,"title":"mobile","guid":"mobile______","url":null},
+        {"id":7,"parent":2,"type":1,"title":"Bookmarks","guid":"a1","url":"http://www.bookmarks.blub"},
+        {"id":8,"parent":2,"type":1,"title":"Recent Tags","guid":"a2","url":"place:type=6&sort=14"},
+        {"id":9,"parent":3,"type":1,"title":"Dashboard","guid":"a3","dateAdded":1650000000123456,"url":"http://www.test.blub"},
+        {"id":10,"parent":3,"type":2,"title":"work","guid":"a4","url":null},
+        {"id":11,"parent":4,"type":2,"title":"ops","guid":"a5","url":null},
+        {"id":12,"parent":10,"type":1,"title":"Jira","guid":"a6","url":"https://jira.test.blub","keywords":"jira j"},
+        {"id":13,"parent":10,"type":3,"title":"","guid":"a7","url":null},
+        {"id":14,"parent":11,"type":1,"title":null,"guid":"a8","url":"https://jira.test.blub"},
+        {"id":15,"parent":5,"type":1,"title":null,"guid":"a9","url":"http://untitled.blub"}
+    ]"#;
+
+    #[test]
+    fn reads_bookmarks_with_folders_tags_and_keywords() {
+        let expected_bookmarks = vec![
+            Bookmark::new("Bookmarks", "http://www.bookmarks.blub")
+                .with_path(vec!["Bookmarks Menu".to_owned()]),
+            Bookmark::new("Dashboard", "http://www.test.blub").with_added(1650000000),
+            Bookmark::new("Jira", "https://jira.test.blub")
+                .with_path(vec!["work".to_owned()])
+                .with_tags(vec!["ops".to_owned()])
+                .with_keywords(vec!["jira".to_owned(), "j".to_owned()]),
+            Bookmark::new("http://untitled.blub", "http://untitled.blub")
+                .with_path(vec!["Other Bookmarks".to_owned()]),
+        ];
+
+        let bookmarks = from_rows(&json::parse(ROWS).unwrap());
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+}
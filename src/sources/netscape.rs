@@ -0,0 +1,227 @@
+use crate::Bookmark;
+
+/// Returns true if the content looks like a Netscape `bookmarks.html` export.
+pub fn is_netscape(contents: &str) -> bool {
+    contents.trim_start().starts_with('<')
+}
+
+/// Reads all bookmarks of a Netscape bookmark file, as exported by browsers
+/// and bookmark services, keeping the `<H3>` folders as path.
+pub fn read_bookmarks(contents: &str) -> Vec<Bookmark> {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    // One entry per open <DL>, holding the folder it belongs to, if any.
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut pending_folder = None;
+    let mut described = None;
+
+    let mut rest = contents;
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        match name.as_str() {
+            "DL" => folders.push(pending_folder.take()),
+            "/DL" => {
+                folders.pop();
+                described = None;
+            }
+            "H3" => {
+                let (text, remaining) = text_until(rest, "</");
+                pending_folder = Some(decode_entities(text.trim()));
+                rest = remaining;
+                described = None;
+            }
+            "A" => {
+                let (text, remaining) = text_until(rest, "</");
+                rest = remaining;
+                described = None;
+                let attributes = attributes(tag);
+                let link = match attribute(&attributes, "HREF") {
+                    Some(link) => link,
+                    None => continue,
+                };
+                let title = decode_entities(text.trim());
+                let name = if title.is_empty() {
+                    link.clone()
+                } else {
+                    title
+                };
+                let path = folders.iter().flatten().cloned().collect();
+                let mut bookmark = Bookmark::new(name, link).with_path(path);
+                if let Some(added) = attribute(&attributes, "ADD_DATE").and_then(|a| a.parse().ok())
+                {
+                    bookmark = bookmark.with_added(added);
+                }
+                if let Some(tags) = attribute(&attributes, "TAGS") {
+                    bookmark = bookmark.with_tags(
+                        tags.split(',')
+                            .map(str::trim)
+                            .filter(|tag| !tag.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                    );
+                }
+                bookmarks.push(bookmark);
+                described = Some(bookmarks.len() - 1);
+            }
+            "DD" => {
+                let (text, remaining) = text_until(rest, "<");
+                rest = remaining;
+                let description = decode_entities(text.trim());
+                if let Some(index) = described.take().filter(|_| !description.is_empty()) {
+                    bookmarks[index] = bookmarks[index].clone().with_description(description);
+                }
+            }
+            _ => {}
+        }
+    }
+    bookmarks
+}
+
+/// Splits off the text up to the given delimiter.
+fn text_until<'a>(contents: &'a str, delimiter: &str) -> (&'a str, &'a str) {
+    match contents.find(delimiter) {
+        Some(end) => (&contents[..end], &contents[end..]),
+        None => (contents, ""),
+    }
+}
+
+/// Parses the attributes of a tag, with upper case names and decoded values.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag.trim_start_matches(|c: char| !c.is_whitespace());
+    loop {
+        rest = rest.trim_start();
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        if name_end == 0 {
+            break;
+        }
+        let name = rest[..name_end].to_ascii_uppercase();
+        rest = rest[name_end..].trim_start();
+        if !rest.starts_with('=') {
+            attributes.push((name, String::new()));
+            continue;
+        }
+        rest = rest[1..].trim_start();
+        let value = match rest.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                let end = rest[1..].find(quote).map_or(rest.len(), |end| end + 1);
+                let value = &rest[1..end];
+                rest = rest.get(end + 1..).unwrap_or_default();
+                value
+            }
+            _ => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                let value = &rest[..end];
+                rest = &rest[end..];
+                value
+            }
+        };
+        attributes.push((name, decode_entities(value)));
+    }
+    attributes
+}
+
+fn attribute(attributes: &[(String, String)], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|(attribute, _)| attribute == name)
+        .map(|(_, value)| value.to_owned())
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let character = entity.and_then(|(entity, _)| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(|c| c == 'x' || c == 'X') {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                std::char::from_u32(code)
+            }
+        });
+        match (character, entity) {
+            (Some(character), Some((_, end))) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::netscape::{is_netscape, read_bookmarks};
+    use crate::Bookmark;
+
+    const BOOKMARKS: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file. -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><A HREF="http://www.test.blub" ADD_DATE="1650000000">Dashboard</A>
+    <DT><H3 ADD_DATE="1650000001">work</H3>
+    <DD>things for work
+    <DL><p>
+        <DT><H3>tickets</H3>
+        <DL><p>
+            <DT><a href='https://jira.test.blub/?a=1&amp;b=2' tags="jira, work">Jira &amp; Confluence</a>
+            <DD>where tickets live
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="http://www.bookmarks.blub" TAGS="">Bookmarks</A>
+    <DT><A>no link</A>
+</DL><p>
+"#;
+
+    #[test]
+    fn detects_netscape_bookmarks() {
+        assert!(is_netscape(BOOKMARKS));
+        assert!(!is_netscape(r#"{"category": []}"#));
+    }
+
+    #[test]
+    fn reads_folders_dates_tags_and_descriptions() {
+        let expected_bookmarks = vec![
+            Bookmark::new("Dashboard", "http://www.test.blub").with_added(1650000000),
+            Bookmark::new("Jira & Confluence", "https://jira.test.blub/?a=1&b=2")
+                .with_path(vec!["work".to_owned(), "tickets".to_owned()])
+                .with_tags(vec!["jira".to_owned(), "work".to_owned()])
+                .with_description("where tickets live"),
+            Bookmark::new("Bookmarks", "http://www.bookmarks.blub"),
+        ];
+
+        let bookmarks = read_bookmarks(BOOKMARKS);
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+}
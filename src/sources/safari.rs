@@ -0,0 +1,84 @@
+use anyhow::Result;
+
+use crate::sources::bplist::{self, Value};
+use crate::Bookmark;
+
+/// The built-in lists of Safari by their title in the plist. Favorites are
+/// stored as `BookmarksBar` and have no folder, the reading list is one.
+const ROOTS: [(&str, Option<&str>); 3] = [
+    ("BookmarksBar", None),
+    ("BookmarksMenu", Some("Bookmarks Menu")),
+    ("com.apple.ReadingList", Some("Reading List")),
+];
+
+/// Reads all bookmarks and the reading list of a Safari `Bookmarks.plist`.
+pub fn read_bookmarks(bytes: &[u8]) -> Result<Vec<Bookmark>> {
+    let plist = bplist::parse(bytes)?;
+    let mut bookmarks = Vec::new();
+    for list in plist.get("Children").as_array() {
+        let title = list.get("Title").as_str().unwrap_or_default();
+        let path = match ROOTS.iter().find(|(root, _)| *root == title) {
+            Some((_, name)) => name.iter().map(|name| name.to_string()).collect(),
+            None if list.get("WebBookmarkType").as_str() == Some("WebBookmarkTypeList") => {
+                vec![title.to_owned()]
+            }
+            None => Vec::new(),
+        };
+        collect(list, &path, &mut bookmarks);
+    }
+    Ok(bookmarks)
+}
+
+fn collect(list: &Value, path: &[String], bookmarks: &mut Vec<Bookmark>) {
+    match list.get("WebBookmarkType").as_str() {
+        Some("WebBookmarkTypeLeaf") => {
+            if let Some(link) = list.get("URLString").as_str() {
+                let name = list
+                    .get("URIDictionary")
+                    .get("title")
+                    .as_str()
+                    .unwrap_or(link);
+                bookmarks.push(Bookmark::new(name, link).with_path(path.to_vec()));
+            }
+        }
+        Some("WebBookmarkTypeList") => {
+            for child in list.get("Children").as_array() {
+                let mut path = path.to_vec();
+                if child.get("WebBookmarkType").as_str() == Some("WebBookmarkTypeList") {
+                    path.push(child.get("Title").as_str().unwrap_or_default().to_owned());
+                }
+                collect(child, &path, bookmarks);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sources::safari::read_bookmarks;
+    use crate::Bookmark;
+
+    #[test]
+    fn reads_bookmarks_and_reading_list() {
+        let expected_bookmarks = vec![
+            Bookmark::new("Dashboard", "http://www.test.blub"),
+            Bookmark::new(
+                "Grafana production dashboard",
+                "https://grafana.test.blub/d/prod",
+            )
+            .with_path(vec!["work".to_owned(), "monitoring".to_owned()]),
+            Bookmark::new("Über uns", "https://www.test.blub/über")
+                .with_path(vec!["Bookmarks Menu".to_owned()]),
+            Bookmark::new("Bookmarks", "http://www.bookmarks.blub")
+                .with_path(vec!["Reading List".to_owned()]),
+        ];
+
+        let bookmarks = read_bookmarks(include_bytes!(
+            "../../tests/fixtures/Safari-Bookmarks.plist"
+        ))
+        .unwrap();
+
+        assert_eq!(bookmarks, expected_bookmarks);
+    }
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use anyhow::{bail, Context, Result};
+use json::JsonValue;
+
+/// The first bytes of every SQLite database file.
+pub const MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// Runs a query on a copy of a database (including the write-ahead log)
+/// using the `sqlite3` command line tool, because browsers keep their
+/// databases locked while running. Returns the rows as json objects.
+pub fn query(database: &Path, query: &str) -> Result<JsonValue> {
+    let copy = env::temp_dir().join(format!(
+        "bookmarks-alfred-workflow-{}.sqlite",
+        process::id()
+    ));
+    let result = copy_database(database, &copy).and_then(|_| run(&copy, query));
+    for file in database_files(&copy) {
+        let _ = fs::remove_file(file);
+    }
+    result
+}
+
+fn database_files(database: &Path) -> Vec<PathBuf> {
+    let wal = format!("{}-wal", database.display());
+    vec![database.to_path_buf(), PathBuf::from(wal)]
+}
+
+fn copy_database(database: &Path, copy: &Path) -> Result<()> {
+    for (from, to) in database_files(database).iter().zip(database_files(copy)) {
+        if from.exists() {
+            fs::copy(from, &to).with_context(|| format!("could not copy {}", from.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn run(database: &Path, query: &str) -> Result<JsonValue> {
+    let output = Command::new("sqlite3")
+        .arg("-json")
+        .arg(database)
+        .arg(query)
+        .output()
+        .context("could not run sqlite3")?;
+    if !output.status.success() {
+        bail!(
+            "sqlite3 failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    if stdout.trim().is_empty() {
+        return Ok(JsonValue::new_array());
+    }
+    Ok(json::parse(&stdout)?)
+}
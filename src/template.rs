@@ -0,0 +1,156 @@
+/// Placeholders filled with all arguments. `{searchTerms}` is used by the
+/// search engines of Chromium browsers.
+const QUERY_PLACEHOLDERS: [&str; 2] = ["{query}", "{searchTerms}"];
+
+/// Returns true if the link has placeholders like `{query}` or `{1}` to fill
+/// with the words typed after the bookmark.
+pub fn is_template(link: &str) -> bool {
+    QUERY_PLACEHOLDERS
+        .iter()
+        .any(|placeholder| link.contains(placeholder))
+        || positional_placeholders(link).next().is_some()
+}
+
+/// Fills the placeholders of a link: `{query}` with all arguments and `{1}`,
+/// `{2}`, ... with the argument at that position, all percent-encoded.
+/// Placeholders without an argument are left empty.
+pub fn fill(link: &str, arguments: &[String]) -> String {
+    let mut filled = link.to_owned();
+    let query = percent_encode(&arguments.join(" "));
+    for placeholder in QUERY_PLACEHOLDERS.iter() {
+        filled = filled.replace(placeholder, &query);
+    }
+    let positions: Vec<usize> = positional_placeholders(link).collect();
+    for position in positions {
+        let argument = arguments.get(position - 1).map_or("", String::as_str);
+        filled = filled.replace(&format!("{{{}}}", position), &percent_encode(argument));
+    }
+    filled
+}
+
+/// Returns true if the link could have been filled from the template, with
+/// anything in place of its placeholders.
+pub fn matches(template: &str, link: &str) -> bool {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some((placeholder, start)) = next_placeholder(rest) {
+        parts.push(&rest[..start]);
+        rest = &rest[start + placeholder.len()..];
+    }
+    parts.push(rest);
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return link == template;
+    }
+    if link.len() < first.len() + last.len() || !link.starts_with(first) || !link.ends_with(last) {
+        return false;
+    }
+    let mut remaining = &link[first.len()..link.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match remaining.find(part) {
+            Some(start) => remaining = &remaining[start + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Returns the first placeholder of a link and its position.
+fn next_placeholder(link: &str) -> Option<(&str, usize)> {
+    link.match_indices('{').find_map(|(start, _)| {
+        let end = start + link[start..].find('}')? + 1;
+        let placeholder = &link[start..end];
+        let number = &placeholder[1..placeholder.len() - 1];
+        let is_placeholder = QUERY_PLACEHOLDERS.contains(&placeholder)
+            || number.parse::<usize>().is_ok_and(|position| position > 0);
+        is_placeholder.then_some((placeholder, start))
+    })
+}
+
+/// Returns the positions of placeholders like `{1}`.
+fn positional_placeholders(link: &str) -> impl Iterator<Item = usize> + '_ {
+    link.split('{').skip(1).filter_map(|part| {
+        let (number, _) = part.split_once('}')?;
+        number.parse().ok().filter(|position| *position > 0)
+    })
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+pub fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{fill, is_template, matches, percent_encode};
+
+    #[test]
+    fn detects_templates() {
+        assert!(is_template("https://jira.test.blub/browse/{query}"));
+        assert!(is_template("https://grafana.test.blub/d/x?var-host={1}"));
+        assert!(is_template("https://search.test.blub/?q={searchTerms}"));
+        assert!(!is_template("https://www.test.blub/{0}"));
+        assert!(!is_template("https://www.test.blub/"));
+    }
+
+    #[test]
+    fn fills_the_query() {
+        let arguments = vec!["ABC-123".to_owned(), "ä&b".to_owned()];
+
+        assert_eq!(
+            fill("https://jira.test.blub/browse/{query}", &arguments),
+            "https://jira.test.blub/browse/ABC-123%20%C3%A4%26b"
+        );
+    }
+
+    #[test]
+    fn fills_positional_arguments() {
+        let arguments = vec!["host 1".to_owned(), "prod".to_owned()];
+
+        assert_eq!(
+            fill(
+                "https://grafana.test.blub/d/x?var-host={1}&var-env={2}&var-x={3}",
+                &arguments
+            ),
+            "https://grafana.test.blub/d/x?var-host=host%201&var-env=prod&var-x="
+        );
+    }
+
+    #[test]
+    fn matches_filled_templates() {
+        let template = "https://grafana.test.blub/d/x?var-host={1}&var-env={2}";
+
+        assert!(matches(
+            template,
+            "https://grafana.test.blub/d/x?var-host=a&var-env=prod"
+        ));
+        assert!(matches(
+            template,
+            "https://grafana.test.blub/d/x?var-host=&var-env="
+        ));
+        assert!(matches(
+            "https://jira.test.blub/browse/{query}",
+            "https://jira.test.blub/browse/ABC-123"
+        ));
+        assert!(!matches(
+            "https://jira.test.blub/browse/{query}",
+            "https://wiki.test.blub/browse/ABC-123"
+        ));
+        assert!(!matches(
+            template,
+            "https://grafana.test.blub/d/x?var-host=a"
+        ));
+    }
+
+    #[test]
+    fn encodes_percent() {
+        assert_eq!(percent_encode("a b/c?d=e"), "a%20b%2Fc%3Fd%3De");
+    }
+}